@@ -0,0 +1,97 @@
+use super::{
+    Rect, MAP_HEIGHT, MAP_PADDING_LEFT, MAP_PADDING_UP, MAP_WIDTH, VIEW_HEIGHT, VIEW_WIDTH,
+};
+
+/// The part of the world shown in the map window. `x`/`y` is the world tile
+/// drawn in the top-left corner of the view.
+#[derive(Default)]
+pub struct Camera {
+    pub x: i32,
+    pub y: i32,
+    /// edge scrolling waits for the first mouse movement, so the cursor
+    /// starting out at (0, 0) doesn't drag the view away
+    pub mouse_moved: bool,
+    /// milliseconds since the last edge scroll step
+    pub scroll_timer: f32,
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        let mut camera = Camera::default();
+        camera.center_on(MAP_WIDTH as i32 / 2, MAP_HEIGHT as i32 / 2);
+
+        camera
+    }
+
+    /// Screen cell of a world tile, which may lie outside the view.
+    pub fn screen_pos(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            x - self.x + MAP_PADDING_LEFT as i32,
+            y - self.y + MAP_PADDING_UP as i32,
+        )
+    }
+
+    /// Screen cell of a world tile, if it is in view.
+    pub fn to_screen(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        if self.in_view(x, y) {
+            Some(self.screen_pos(x, y))
+        } else {
+            None
+        }
+    }
+
+    /// World tile under a screen cell, if the cell is inside the view.
+    pub fn to_world(&self, screen_x: i32, screen_y: i32) -> Option<(i32, i32)> {
+        let x = screen_x - MAP_PADDING_LEFT as i32;
+        let y = screen_y - MAP_PADDING_UP as i32;
+        if x < 0 || y < 0 || x >= VIEW_WIDTH as i32 || y >= VIEW_HEIGHT as i32 {
+            return None;
+        }
+
+        Some((x + self.x, y + self.y))
+    }
+
+    pub fn in_view(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && x < self.x + VIEW_WIDTH as i32
+            && y >= self.y
+            && y < self.y + VIEW_HEIGHT as i32
+    }
+
+    pub fn scroll(&mut self, dx: i32, dy: i32) {
+        self.x = (self.x + dx).clamp(0, MAP_WIDTH as i32 - VIEW_WIDTH as i32);
+        self.y = (self.y + dy).clamp(0, MAP_HEIGHT as i32 - VIEW_HEIGHT as i32);
+    }
+
+    pub fn center_on(&mut self, x: i32, y: i32) {
+        self.x = x - VIEW_WIDTH as i32 / 2;
+        self.y = y - VIEW_HEIGHT as i32 / 2;
+        self.scroll(0, 0);
+    }
+
+    /// World tile in the middle of the view.
+    pub fn center(&self) -> (i32, i32) {
+        (
+            self.x + VIEW_WIDTH as i32 / 2,
+            self.y + VIEW_HEIGHT as i32 / 2,
+        )
+    }
+
+    /// Scrolls just far enough to bring all of `rect` into view.
+    pub fn follow(&mut self, rect: &Rect) {
+        let mut dx = 0;
+        if rect.x1 < self.x {
+            dx = rect.x1 - self.x;
+        } else if rect.x2 > self.x + VIEW_WIDTH as i32 {
+            dx = rect.x2 - (self.x + VIEW_WIDTH as i32);
+        }
+        let mut dy = 0;
+        if rect.y1 < self.y {
+            dy = rect.y1 - self.y;
+        } else if rect.y2 > self.y + VIEW_HEIGHT as i32 {
+            dy = rect.y2 - (self.y + VIEW_HEIGHT as i32);
+        }
+
+        self.scroll(dx, dy);
+    }
+}
@@ -0,0 +1,204 @@
+use chrono::prelude::*;
+use std::ops::{Deref, DerefMut};
+
+pub const GAME_SPEEDS: [u32; 4] = [1, 2, 5, 10];
+
+/// Source of simulation time. Timestamps are unix milliseconds so that they
+/// stay comparable with the ones stored in save files.
+pub trait Clock: Send + Sync {
+    fn now_millis(&self) -> i64;
+
+    fn now(&self) -> i64 {
+        self.now_millis().div_euclid(1000)
+    }
+
+    fn speed(&self) -> u32 {
+        1
+    }
+
+    fn set_speed(&mut self, _speed: u32) {}
+
+    fn is_paused(&self) -> bool {
+        false
+    }
+
+    fn set_paused(&mut self, _paused: bool) {}
+
+    /// Carries on from `millis`, e.g. the time a save was made.
+    fn set_now_millis(&mut self, millis: i64);
+
+    /// Called once per frame; clocks that don't follow real time move here.
+    fn tick(&mut self) {}
+}
+
+/// Real time, exactly as the system reports it.
+pub struct WallClock;
+
+impl Clock for WallClock {
+    fn now_millis(&self) -> i64 {
+        Local::now().timestamp_millis()
+    }
+
+    fn set_now_millis(&mut self, _millis: i64) {}
+}
+
+/// Moves a fixed step (times the speed) every frame, however long the frame
+/// took, so a run plays out the same on any machine. Tests move it by hand.
+pub struct ManualClock {
+    current: i64,
+    step_millis: i64,
+    speed: u32,
+    paused: bool,
+}
+
+impl ManualClock {
+    pub fn new(start_millis: i64, step_millis: i64) -> Self {
+        ManualClock {
+            current: start_millis,
+            step_millis,
+            speed: 1,
+            paused: false,
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_millis(&self) -> i64 {
+        self.current
+    }
+
+    fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    fn set_speed(&mut self, speed: u32) {
+        self.speed = speed;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    fn set_now_millis(&mut self, millis: i64) {
+        self.current = millis;
+    }
+
+    fn tick(&mut self) {
+        if !self.paused {
+            self.current += self.step_millis * self.speed as i64;
+        }
+    }
+}
+
+/// Wall time multiplied by a speed factor, with support for pausing.
+pub struct ScaledClock {
+    wall_anchor: i64,
+    sim_anchor: i64,
+    speed: u32,
+    paused: bool,
+}
+
+impl ScaledClock {
+    pub fn new() -> Self {
+        let now = Local::now().timestamp_millis();
+        ScaledClock {
+            wall_anchor: now,
+            sim_anchor: now,
+            speed: 1,
+            paused: false,
+        }
+    }
+
+    /// Pins the current simulation time so speed changes only affect the future.
+    fn rebase(&mut self) {
+        self.sim_anchor = self.now_millis();
+        self.wall_anchor = Local::now().timestamp_millis();
+    }
+}
+
+impl Default for ScaledClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ScaledClock {
+    fn now_millis(&self) -> i64 {
+        if self.paused {
+            return self.sim_anchor;
+        }
+
+        let wall_elapsed = Local::now().timestamp_millis() - self.wall_anchor;
+        self.sim_anchor + wall_elapsed * self.speed as i64
+    }
+
+    fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    fn set_speed(&mut self, speed: u32) {
+        self.rebase();
+        self.speed = speed;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn set_now_millis(&mut self, millis: i64) {
+        self.sim_anchor = millis;
+        self.wall_anchor = Local::now().timestamp_millis();
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.rebase();
+        self.paused = paused;
+    }
+}
+
+/// The clock resource every timed system reads from.
+pub struct GameClock(Box<dyn Clock>);
+
+impl GameClock {
+    pub fn new<C: Clock + 'static>(clock: C) -> Self {
+        GameClock(Box::new(clock))
+    }
+}
+
+impl Deref for GameClock {
+    type Target = dyn Clock;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl DerefMut for GameClock {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock_steps_at_its_speed_until_paused() {
+        let mut clock = ManualClock::new(0, 100);
+        clock.tick();
+        assert_eq!(clock.now_millis(), 100);
+
+        clock.set_speed(5);
+        clock.tick();
+        assert_eq!(clock.now_millis(), 600);
+
+        clock.set_paused(true);
+        clock.tick();
+        assert_eq!(clock.now_millis(), 600);
+    }
+}
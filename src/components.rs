@@ -1,11 +1,14 @@
 use rltk::RGB;
 use serde::{Deserialize, Serialize};
+#[allow(deprecated)]
 use specs::error::NoError;
 use specs::prelude::*;
 use specs::saveload::{ConvertSaveload, Marker};
 use specs_derive::*;
 
-use super::{Rect, ResourceType};
+use std::collections::{HashMap, VecDeque};
+
+use super::{Map, Rect, ResourceType};
 
 #[derive(Component, ConvertSaveload)]
 pub struct Renderable {
@@ -21,28 +24,303 @@ pub struct Building {
     pub level: i32,
 }
 
+/// A building being upgraded to `level`, which it reaches at `finish_at`.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct Renovation {
+    pub level: i32,
+    pub seconds: i32,
+    pub finish_at: i64, // second
+    /// What was paid for it, all of which is handed back if it is cancelled.
+    pub cost: HashMap<ResourceType, i32>,
+}
+
+/// A building waiting in the build queue, or being built once `finish_at`
+/// is set. It only turns into a working building when it is finished.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct ConstructionSite {
+    pub name: String,
+    pub rect: Rect,
+    pub seconds: i32,           // a builder needs to finish it
+    pub queued_at: i64,         // second
+    pub finish_at: Option<i64>, // second
+    /// What was paid for it, all of which is handed back if it is cancelled.
+    pub cost: HashMap<ResourceType, i32>,
+}
+
 #[derive(Component, ConvertSaveload, Debug)]
 pub struct Name {
     pub name: String,
 }
 
-#[derive(Component, ConvertSaveload)]
+/// Fields that are missing from a save fall back to their defaults, so adding
+/// one doesn't break the saves made before it.
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct PlayerStats {
-    pub food: ResourceInfo,
-    pub wood: ResourceInfo,
-    pub stone: ResourceInfo,
+    #[serde(default)]
+    pub resources: HashMap<ResourceType, ResourceInfo>,
     pub next_refresh: i64, // second
+    #[serde(default = "first_level")]
+    pub level: i32,
+    #[serde(default)]
+    pub xp: i32,
+    #[serde(default)]
+    pub production_xp_pool: i32, // resources produced that have not become xp yet
+    #[serde(default)]
+    pub population: i32,
+    #[serde(default)]
+    pub housing: i32, // most citizens the settlement can hold
+    #[serde(default)]
+    pub growth_progress: i32, // seconds towards the next citizen
+    #[serde(default)]
+    pub starving_seconds: i32, // how long the settlement has been out of food
+    #[serde(default)]
+    pub founded_at: i64, // second
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+fn first_level() -> i32 {
+    1
+}
+
+impl PlayerStats {
+    pub fn amount(&self, id: &str) -> i32 {
+        self.resources.get(id).map_or(0, |info| info.amount)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ResourceInfo {
     pub amount: i32,
     pub max_amount: i32,
     pub rate: i32, // per sec
 }
 
-#[derive(Component, Copy, Clone, ConvertSaveload)]
+/// Seconds between two samples of the recent history (10 minutes in all).
+pub const RECENT_SAMPLE_SECONDS: i64 = 5;
+/// Seconds between two samples of the long-term history (6 hours in all).
+pub const LONG_TERM_SAMPLE_SECONDS: i64 = 180;
+/// Samples each history keeps.
+pub const HISTORY_SAMPLES: usize = 120;
+
+/// One resource at one moment.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub struct ResourceSample {
+    pub amount: i32,
+    pub rate: i32, // per sec
+    pub cap: i32,
+}
+
+/// Snapshots of every resource, one every `interval` seconds. Once full,
+/// each new snapshot pushes out the oldest.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SampleBuffer {
+    pub interval: i64, // second
+    pub next_at: i64,  // second
+    pub samples: VecDeque<HashMap<ResourceType, ResourceSample>>,
+}
+
+impl SampleBuffer {
+    pub fn new(interval: i64) -> Self {
+        SampleBuffer {
+            interval,
+            next_at: 0,
+            samples: VecDeque::with_capacity(HISTORY_SAMPLES),
+        }
+    }
+
+    /// Takes a snapshot of `stats` if one is due at `current`.
+    pub fn record(&mut self, stats: &PlayerStats, current: i64) {
+        if current < self.next_at {
+            return;
+        }
+
+        if self.samples.len() >= HISTORY_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(
+            stats
+                .resources
+                .iter()
+                .map(|(resource_type, info)| {
+                    let sample = ResourceSample {
+                        amount: info.amount,
+                        rate: info.rate,
+                        cap: info.max_amount,
+                    };
+                    (resource_type.clone(), sample)
+                })
+                .collect(),
+        );
+        self.next_at = current + self.interval;
+    }
+}
+
+/// Where the player's resources have been over the last minutes and hours,
+/// and how much production was thrown away at the storage caps.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct ResourceHistory {
+    pub recent: SampleBuffer,
+    pub long_term: SampleBuffer,
+    pub lost_to_cap: HashMap<ResourceType, i64>,
+}
+
+impl Default for ResourceHistory {
+    fn default() -> Self {
+        ResourceHistory {
+            recent: SampleBuffer::new(RECENT_SAMPLE_SECONDS),
+            long_term: SampleBuffer::new(LONG_TERM_SAMPLE_SECONDS),
+            lost_to_cap: HashMap::new(),
+        }
+    }
+}
+
+/// Extra capacity a storage building adds on top of the base cap.
+#[derive(Component, Clone, ConvertSaveload)]
+pub struct Capacity {
+    pub capacity: HashMap<ResourceType, i32>,
+}
+
+#[derive(Component, Clone, ConvertSaveload)]
 pub struct Generator {
     pub rate: i32, // per sec
     pub resource_type: ResourceType,
+    pub yield_percent: i32,              // from the terrain around it
+    pub harvest_radius: Option<i32>,     // draws from deposits instead of thin air
+    pub exhausted: bool,                 // no deposits left in range
+    pub renovation_percent: Option<i32>, // of the rate kept while being upgraded
+}
+
+/// Room for citizens a housing building adds on top of the base housing.
+#[derive(Component, Copy, Clone, ConvertSaveload)]
+pub struct Housing {
+    pub capacity: i32,
+}
+
+/// Citizens working in a building; output scales with `assigned / slots`.
+#[derive(Component, Copy, Clone, ConvertSaveload)]
+pub struct Workforce {
+    pub slots: i32,
+    pub assigned: i32,
+}
+
+/// Resources a building eats per second, e.g. the Army's food.
+#[derive(Component, Clone, ConvertSaveload)]
+pub struct Upkeep {
+    pub costs: HashMap<ResourceType, i32>,
+}
+
+/// Soldiers stationed in a building that trains them over time.
+#[derive(Component, Clone, ConvertSaveload)]
+pub struct Garrison {
+    pub soldiers: i32,
+    pub capacity: i32,
+    pub seconds: i32, // to train one soldier
+    pub cost: HashMap<ResourceType, i32>,
+    pub next_soldier: i64, // second
+}
+
+/// Where a unit stands on the map; buildings use their `rect` instead.
+#[derive(Component, ConvertSaveload, Clone, Copy)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A raid party marching on the settlement.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct Raider {
+    pub strength: i32,
+    pub next_move: i64, // second
+}
+
+/// When the next raid arrives; lives on the player entity.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct RaidSchedule {
+    pub next_raid: i64, // second
+    pub raids: i32,     // raids so far
+}
+
+/// A raid party that reached a building and is about to fight.
+#[derive(Component, Debug)]
+pub struct WantsToRaid {
+    pub target: Entity,
+}
+
+/// Levels a building loses to a raid; at level 0 it is destroyed.
+#[derive(Component, Debug)]
+pub struct SufferDamage {
+    pub amount: i32,
+}
+
+#[derive(PartialEq, Serialize, Deserialize, Copy, Clone, Debug)]
+pub enum ConverterStatus {
+    Running,
+    Starved, // an input ran out
+    Blocked, // an output is at its cap
+    Renovating,
+}
+
+/// Turns `inputs` into `outputs` every second, e.g. wood into planks.
+#[derive(Component, Clone, ConvertSaveload)]
+pub struct Converter {
+    pub inputs: HashMap<ResourceType, i32>,  // per sec
+    pub outputs: HashMap<ResourceType, i32>, // per sec
+    pub status: ConverterStatus,
+    pub renovating: bool, // stands still while being upgraded
+}
+
+/// Marker type for entities that are written to the save file.
+pub struct SerializeMe;
+
+/// Carries the map resource and the clock through serialization; it only
+/// lives for the duration of a save or a load.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct SerializationHelper {
+    pub map: Map,
+    pub sim_time: i64,  // millisecond, game clock when saved
+    pub wall_time: i64, // millisecond, real clock when saved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_with_wood(amount: i32) -> PlayerStats {
+        let mut stats: PlayerStats = serde_json::from_str(r#"{"next_refresh": 0}"#).unwrap();
+        stats.resources.insert(
+            "wood".to_string(),
+            ResourceInfo {
+                amount,
+                max_amount: 1000,
+                rate: 0,
+            },
+        );
+        stats
+    }
+
+    #[test]
+    fn sample_buffer_waits_for_the_interval() {
+        let mut buffer = SampleBuffer::new(5);
+        buffer.record(&stats_with_wood(1), 100);
+        buffer.record(&stats_with_wood(2), 104);
+        assert_eq!(buffer.samples.len(), 1);
+
+        buffer.record(&stats_with_wood(3), 105);
+        assert_eq!(buffer.samples.len(), 2);
+        assert_eq!(buffer.next_at, 110);
+    }
+
+    #[test]
+    fn sample_buffer_drops_the_oldest_once_full() {
+        let mut buffer = SampleBuffer::new(1);
+        for second in 0..HISTORY_SAMPLES as i64 + 3 {
+            buffer.record(&stats_with_wood(second as i32), second);
+        }
+
+        assert_eq!(buffer.samples.len(), HISTORY_SAMPLES);
+        assert_eq!(buffer.samples.front().unwrap()["wood"].amount, 3);
+        assert_eq!(
+            buffer.samples.back().unwrap()["wood"].amount,
+            HISTORY_SAMPLES as i32 + 2
+        );
+    }
 }
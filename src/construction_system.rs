@@ -0,0 +1,277 @@
+use specs::prelude::*;
+use std::cmp::max;
+
+use super::components::*;
+use crate::gamelog::{self, LogCategory};
+use crate::progression::{self, LevelTable};
+use crate::{resource_system, spawner, utils, ConstructionManifest, GameClock, Rect};
+
+/// Puts idle builders to work on the construction sites that have waited
+/// longest.
+pub struct ConstructionSystem {}
+
+impl<'a> System<'a> for ConstructionSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, GameClock>,
+        ReadExpect<'a, ConstructionManifest>,
+        WriteStorage<'a, ConstructionSite>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, clock, manifest, mut sites) = data;
+
+        let current = clock.now();
+        let busy = sites.join().filter(|site| site.finish_at.is_some()).count() as i32;
+        let idle = max(manifest.builder_slots - busy, 0) as usize;
+
+        let mut waiting: Vec<(Entity, &ConstructionSite)> = (&entities, &sites)
+            .join()
+            .filter(|(_, site)| site.finish_at.is_none())
+            .collect();
+        waiting.sort_by_key(|(entity, site)| queue_order(*entity, site));
+        let starting: Vec<Entity> = waiting
+            .into_iter()
+            .take(idle)
+            .map(|(entity, _)| entity)
+            .collect();
+
+        for entity in starting {
+            let site = sites.get_mut(entity).unwrap();
+            site.finish_at = Some(current + site.seconds as i64);
+        }
+    }
+}
+
+/// Sites being built come first, then the waiting ones, oldest first.
+fn queue_order(entity: Entity, site: &ConstructionSite) -> (bool, i64, u32) {
+    (site.finish_at.is_none(), site.queued_at, entity.id())
+}
+
+/// Every construction site in queue order.
+pub fn build_queue(ecs: &World) -> Vec<Entity> {
+    let entities = ecs.entities();
+    let sites = ecs.read_storage::<ConstructionSite>();
+
+    let mut queue: Vec<(Entity, &ConstructionSite)> = (&entities, &sites).join().collect();
+    queue.sort_by_key(|(entity, site)| queue_order(*entity, site));
+
+    queue.into_iter().map(|(entity, _)| entity).collect()
+}
+
+/// How far along a site is, from 0 to 100.
+pub fn progress_percent(site: &ConstructionSite, current: i64) -> i32 {
+    match site.finish_at {
+        None => 0,
+        Some(_) if site.seconds <= 0 => 100,
+        Some(finish_at) => {
+            let left = (finish_at - current).clamp(0, site.seconds as i64);
+            100 - (left * 100 / site.seconds as i64) as i32
+        }
+    }
+}
+
+/// Turns every finished construction site into a working building.
+pub fn finish_construction(ecs: &mut World) {
+    let current = ecs.fetch::<GameClock>().now();
+    let finished: Vec<(Entity, String, Rect)> = {
+        let entities = ecs.entities();
+        let sites = ecs.read_storage::<ConstructionSite>();
+        (&entities, &sites)
+            .join()
+            .filter(|(_, site)| site.finish_at.is_some_and(|at| at <= current))
+            .map(|(entity, site)| (entity, site.name.clone(), site.rect))
+            .collect()
+    };
+    if finished.is_empty() {
+        return;
+    }
+
+    for (entity, name, rect) in finished {
+        ecs.delete_entity(entity)
+            .expect("Unable to delete the construction site");
+        let detail = match ecs.fetch::<ConstructionManifest>().building(&name) {
+            Some(detail) => detail.clone(),
+            None => continue,
+        };
+
+        let building = spawner::spawn_building(ecs, &detail, rect.x1, rect.y1);
+        utils::assign_workers(ecs, building, i32::MAX);
+        gamelog::log(
+            ecs,
+            LogCategory::Construction,
+            format!("The {} is finished.", name),
+        );
+        progression::reward_player(ecs, ecs.fetch::<LevelTable>().construction_xp);
+    }
+
+    resource_system::refresh_rates(ecs);
+}
+
+/// Brings every building whose renovation is over to its next level.
+pub fn finish_upgrades(ecs: &mut World) {
+    let current = ecs.fetch::<GameClock>().now();
+    let finished: Vec<Entity> = {
+        let entities = ecs.entities();
+        let renovations = ecs.read_storage::<Renovation>();
+        (&entities, &renovations)
+            .join()
+            .filter(|(_, renovation)| renovation.finish_at <= current)
+            .map(|(entity, _)| entity)
+            .collect()
+    };
+    if finished.is_empty() {
+        return;
+    }
+
+    for entity in finished {
+        let (name, next_level) = {
+            let names = ecs.read_storage::<Name>();
+            let renovations = ecs.read_storage::<Renovation>();
+            match (names.get(entity), renovations.get(entity)) {
+                (Some(name), Some(renovation)) => (name.name.clone(), renovation.level),
+                _ => continue,
+            }
+        };
+        // the manifest may have been reloaded while the upgrade was under way
+        let detail = ecs
+            .fetch::<ConstructionManifest>()
+            .building(&name)
+            .filter(|detail| detail.levels.contains_key(&next_level))
+            .cloned();
+        let detail = match detail {
+            Some(detail) => detail,
+            None => {
+                utils::cancel_upgrade(
+                    ecs,
+                    entity,
+                    &format!("level {} is no longer in the manifest", next_level),
+                );
+                continue;
+            }
+        };
+
+        ecs.write_storage::<Renovation>().remove(entity);
+        utils::apply_level(ecs, entity, &detail, next_level);
+        gamelog::log(
+            ecs,
+            LogCategory::Construction,
+            format!("The {} reached level {}.", name, next_level),
+        );
+        progression::reward_player(ecs, ecs.fetch::<LevelTable>().upgrade_xp);
+    }
+
+    resource_system::refresh_rates(ecs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{advance, now, test_world};
+    use std::collections::HashMap;
+
+    fn farm(ecs: &World) -> crate::BuildingDetail {
+        ecs.fetch::<ConstructionManifest>()
+            .building("Farm")
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn construction_finishes_once_its_time_is_up() {
+        let mut ecs = test_world();
+        let detail = farm(&ecs);
+        let site = spawner::spawn_construction_site(&mut ecs, &detail, 10, 10);
+        ConstructionSystem {}.run_now(&ecs);
+        ecs.maintain();
+
+        advance(&mut ecs, detail.levels[&0].build_seconds as i64 - 1);
+        finish_construction(&mut ecs);
+        ecs.maintain();
+        assert!(ecs.is_alive(site));
+
+        advance(&mut ecs, 1);
+        finish_construction(&mut ecs);
+        ecs.maintain();
+        assert!(!ecs.is_alive(site));
+        let names = ecs.read_storage::<Name>();
+        let buildings = ecs.read_storage::<Building>();
+        assert!((&names, &buildings)
+            .join()
+            .any(|(name, building)| name.name == "Farm" && building.rect.x1 == 10));
+    }
+
+    #[test]
+    fn upgrade_finishes_once_its_time_is_up() {
+        let mut ecs = test_world();
+        let detail = farm(&ecs);
+        let building = spawner::spawn_building(&mut ecs, &detail, 10, 10);
+        let seconds = detail.levels[&1].build_seconds;
+        let finish_at = now(&ecs) + seconds as i64;
+        ecs.write_storage::<Renovation>()
+            .insert(
+                building,
+                Renovation {
+                    level: 1,
+                    seconds,
+                    finish_at,
+                    cost: HashMap::new(),
+                },
+            )
+            .unwrap();
+
+        advance(&mut ecs, seconds as i64 - 1);
+        finish_upgrades(&mut ecs);
+        assert!(ecs.read_storage::<Renovation>().contains(building));
+        assert_eq!(
+            ecs.read_storage::<Building>().get(building).unwrap().level,
+            0
+        );
+
+        advance(&mut ecs, 1);
+        finish_upgrades(&mut ecs);
+        assert!(!ecs.read_storage::<Renovation>().contains(building));
+        assert_eq!(
+            ecs.read_storage::<Building>().get(building).unwrap().level,
+            1
+        );
+    }
+
+    #[test]
+    fn upgrade_to_a_missing_level_is_refunded() {
+        let mut ecs = test_world();
+        let detail = farm(&ecs);
+        let building = spawner::spawn_building(&mut ecs, &detail, 10, 10);
+        let player = *ecs.fetch::<Entity>();
+        let food_before = ecs
+            .read_storage::<PlayerStats>()
+            .get(player)
+            .unwrap()
+            .amount("food");
+        let finish_at = now(&ecs);
+        ecs.write_storage::<Renovation>()
+            .insert(
+                building,
+                Renovation {
+                    level: 7,
+                    seconds: 0,
+                    finish_at,
+                    cost: HashMap::from([("food".to_string(), 100)]),
+                },
+            )
+            .unwrap();
+
+        finish_upgrades(&mut ecs);
+        assert!(!ecs.read_storage::<Renovation>().contains(building));
+        assert_eq!(
+            ecs.read_storage::<Building>().get(building).unwrap().level,
+            0
+        );
+        let food = ecs
+            .read_storage::<PlayerStats>()
+            .get(player)
+            .unwrap()
+            .amount("food");
+        assert_eq!(food, food_before + 100);
+    }
+}
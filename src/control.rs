@@ -1,19 +1,176 @@
-use rltk::{console, Point, Rltk, VirtualKeyCode};
+use rltk::Rltk;
 use specs::prelude::*;
-use std::cmp::{max, min};
 
-use super::RunState;
+use crate::keybindings::{Action, KeyBindings};
+
+use super::{
+    construction_system, gui, utils, Building, Camera, GameClock, RunState, GAME_SPEEDS,
+    MAP_PADDING_LEFT, MAP_PADDING_UP, VIEW_HEIGHT, VIEW_WIDTH,
+};
+
+/// Tiles the scroll keys move the camera by.
+pub const SCROLL_STEP: i32 = 4;
+/// Milliseconds between two steps while the mouse rests on the map edge.
+pub const EDGE_SCROLL_INTERVAL: f32 = 30.0;
+
+/// The mouse position this frame. Hovering only takes over a selection from
+/// the keyboard once the mouse has actually moved.
+#[derive(Default)]
+pub struct MouseCursor {
+    pub x: i32,
+    pub y: i32,
+    pub moved: bool,
+}
+
+pub fn track_mouse(ecs: &mut World, ctx: &Rltk) {
+    let (x, y) = ctx.mouse_pos();
+    let mut cursor = ecs.write_resource::<MouseCursor>();
+    cursor.moved = (x, y) != (cursor.x, cursor.y);
+    cursor.x = x;
+    cursor.y = y;
+}
 
 pub fn player_input(ecs: &mut World, ctx: &mut Rltk) -> RunState {
-    match ctx.key {
-        None => return RunState::Idle, // Nothing happened
-        Some(key) => match key {
-            VirtualKeyCode::C => return RunState::ConstructionMenu { selected_idx: 0 },
+    // clicking a building opens its info panel, clicking a site the build queue
+    if ctx.left_click {
+        let (mouse_x, mouse_y) = ctx.mouse_pos();
+        let tile = ecs.fetch::<Camera>().to_world(mouse_x, mouse_y);
+        if let Some((x, y)) = tile {
+            if let Some(entity) = utils::building_under(ecs, x, y) {
+                let rect = ecs.read_storage::<Building>().get(entity).unwrap().rect;
+                return RunState::ConstructionSelecting {
+                    x: rect.x1,
+                    y: rect.y1,
+                };
+            }
+            if let Some(entity) = utils::site_under(ecs, x, y) {
+                let queue = construction_system::build_queue(ecs);
+                let selected_idx = queue.iter().position(|e| *e == entity).unwrap_or(0);
+                return RunState::BuildQueue { selected_idx };
+            }
+        }
+    }
+
+    let action = ecs.fetch::<KeyBindings>().action(
+        ctx.key,
+        &[
+            Action::OpenBuildMenu,
+            Action::SelectBuilding,
+            Action::ShowBuildQueue,
+            Action::SaveAndExit,
+            Action::ShowBindings,
+            Action::ShowLog,
+            Action::ShowStatistics,
+            Action::Pause,
+            Action::Speed1,
+            Action::Speed2,
+            Action::Speed3,
+            Action::Speed4,
+        ],
+    );
+    match action {
+        None => RunState::Idle, // Nothing happened
+        Some(action) => match action {
+            Action::OpenBuildMenu => RunState::ConstructionMenu { selected_idx: 0 },
+
+            Action::SelectBuilding => RunState::ConstructionSelecting { x: 0, y: 0 },
+
+            Action::ShowBuildQueue => RunState::BuildQueue { selected_idx: 0 },
+
+            Action::SaveAndExit => RunState::SaveGame,
+
+            Action::ShowBindings => RunState::KeyBindings,
+
+            Action::ShowLog => RunState::MessageLog { scroll: 0 },
+
+            Action::ShowStatistics => RunState::Statistics {
+                resource_idx: 0,
+                long_term: false,
+            },
 
-            VirtualKeyCode::S => return RunState::ConstructionSelecting { x: 0, y: 0 },
-            _ => return RunState::Idle,
+            Action::Pause => {
+                let mut clock = ecs.write_resource::<GameClock>();
+                let paused = clock.is_paused();
+                clock.set_paused(!paused);
+                RunState::Idle
+            }
+            Action::Speed1 => set_speed(ecs, GAME_SPEEDS[0]),
+            Action::Speed2 => set_speed(ecs, GAME_SPEEDS[1]),
+            Action::Speed3 => set_speed(ecs, GAME_SPEEDS[2]),
+            Action::Speed4 => set_speed(ecs, GAME_SPEEDS[3]),
+            _ => RunState::Idle,
         },
     }
+}
 
+fn set_speed(ecs: &mut World, speed: u32) -> RunState {
+    ecs.write_resource::<GameClock>().set_speed(speed);
     RunState::Idle
 }
+
+/// Scrolls the camera with the scroll keys, or while the mouse rests on the
+/// edge of the map window.
+pub fn scroll_camera(ecs: &mut World, ctx: &mut Rltk) {
+    let bindings = ecs.fetch::<KeyBindings>();
+    let mut camera = ecs.write_resource::<Camera>();
+
+    let action = bindings.action(
+        ctx.key,
+        &[
+            Action::ScrollLeft,
+            Action::ScrollRight,
+            Action::ScrollUp,
+            Action::ScrollDown,
+        ],
+    );
+    match action {
+        Some(Action::ScrollLeft) => camera.scroll(-SCROLL_STEP, 0),
+        Some(Action::ScrollRight) => camera.scroll(SCROLL_STEP, 0),
+        Some(Action::ScrollUp) => camera.scroll(0, -SCROLL_STEP),
+        Some(Action::ScrollDown) => camera.scroll(0, SCROLL_STEP),
+        _ => {}
+    }
+
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    if (mouse_x, mouse_y) != (0, 0) {
+        camera.mouse_moved = true;
+    }
+
+    // clicking the minimap, or pressing the jump key over it, moves the view there
+    if let Some((x, y)) = gui::minimap_to_world(mouse_x, mouse_y) {
+        if ctx.left_click || bindings.pressed(ctx.key, Action::MinimapJump) {
+            camera.center_on(x, y);
+            return;
+        }
+    }
+    let right = (MAP_PADDING_LEFT + VIEW_WIDTH) as i32;
+    let bottom = (MAP_PADDING_UP + VIEW_HEIGHT) as i32;
+    if !camera.mouse_moved || mouse_x > right || mouse_y > bottom {
+        return;
+    }
+
+    let dx = if mouse_x <= MAP_PADDING_LEFT as i32 {
+        -1
+    } else if mouse_x >= right - 1 {
+        1
+    } else {
+        0
+    };
+    let dy = if mouse_y <= MAP_PADDING_UP as i32 {
+        -1
+    } else if mouse_y >= bottom - 1 {
+        1
+    } else {
+        0
+    };
+    if dx == 0 && dy == 0 {
+        camera.scroll_timer = 0.0;
+        return;
+    }
+
+    camera.scroll_timer += ctx.frame_time_ms;
+    while camera.scroll_timer >= EDGE_SCROLL_INTERVAL {
+        camera.scroll_timer -= EDGE_SCROLL_INTERVAL;
+        camera.scroll(dx, dy);
+    }
+}
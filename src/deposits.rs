@@ -0,0 +1,169 @@
+use std::cmp::{max, min};
+
+use crate::{ConstructionManifest, Map, Rect, Regrowth};
+
+/// Map indices of the tiles under `rect` and up to `radius` around it,
+/// nearest first.
+fn tiles_in_range(map: &Map, rect: &Rect, radius: i32) -> Vec<usize> {
+    let mut tiles = Vec::new();
+    for x in max(rect.x1 - radius, 0)..min(rect.x2 + radius, map.width) {
+        for y in max(rect.y1 - radius, 0)..min(rect.y2 + radius, map.height) {
+            let dx = max(max(rect.x1 - x, x - (rect.x2 - 1)), 0);
+            let dy = max(max(rect.y1 - y, y - (rect.y2 - 1)), 0);
+            tiles.push((max(dx, dy), map.xy_idx(x, y)));
+        }
+    }
+    tiles.sort();
+
+    tiles.into_iter().map(|(_, idx)| idx).collect()
+}
+
+/// Amount of `resource` left in the deposits around `rect`.
+pub fn remaining(
+    map: &Map,
+    manifest: &ConstructionManifest,
+    resource: &str,
+    rect: &Rect,
+    radius: i32,
+) -> i32 {
+    tiles_in_range(map, rect, radius)
+        .into_iter()
+        .filter_map(|idx| {
+            manifest
+                .deposit(map.tiles[idx])
+                .filter(|deposit| deposit.resource == resource)
+                .map(|deposit| max(deposit.amount - map.drawn[idx], 0))
+        })
+        .sum()
+}
+
+/// Takes up to `wanted` of `resource` from the deposits around `rect`, nearest
+/// tiles first. Exhausted tiles turn into their depleted tile and, if they are
+/// renewable, are scheduled to grow back. Returns the amount taken and whether
+/// any tile changed.
+pub fn harvest(
+    map: &mut Map,
+    manifest: &ConstructionManifest,
+    resource: &str,
+    rect: &Rect,
+    radius: i32,
+    wanted: i32,
+    current: i64,
+) -> (i32, bool) {
+    let mut taken = 0;
+    let mut changed = false;
+    for idx in tiles_in_range(map, rect, radius) {
+        if taken >= wanted {
+            break;
+        }
+
+        let deposit = match manifest.deposit(map.tiles[idx]) {
+            Some(deposit) if deposit.resource == resource => deposit,
+            _ => continue,
+        };
+        let amount = min(wanted - taken, max(deposit.amount - map.drawn[idx], 0));
+        map.drawn[idx] += amount;
+        taken += amount;
+
+        if map.drawn[idx] >= deposit.amount {
+            map.set_tile(idx, deposit.depleted_tile);
+            map.drawn[idx] = 0;
+            if let Some(seconds) = deposit.regrow_seconds {
+                map.regrowing.push(Regrowth {
+                    idx,
+                    tile: deposit.tile,
+                    at: current + seconds as i64,
+                });
+            }
+            changed = true;
+        }
+    }
+
+    (taken, changed)
+}
+
+/// Turns exhausted tiles whose time has come back into full deposits.
+/// Returns whether any tile changed.
+pub fn regrow(map: &mut Map, current: i64) -> bool {
+    let (ready, waiting): (Vec<Regrowth>, Vec<Regrowth>) = map
+        .regrowing
+        .drain(..)
+        .partition(|regrowth| regrowth.at <= current);
+    map.regrowing = waiting;
+
+    for regrowth in ready.iter() {
+        map.set_tile(regrowth.idx, regrowth.tile);
+        map.drawn[regrowth.idx] = 0;
+    }
+
+    !ready.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{manifest, TileType, MANIFEST_PATH};
+
+    fn setup(tiles: &[(i32, i32, TileType)]) -> (Map, ConstructionManifest) {
+        let mut map = Map::new();
+        for (x, y, tile) in tiles {
+            let idx = map.xy_idx(*x, *y);
+            map.set_tile(idx, *tile);
+        }
+        let manifest = manifest::load_manifest(MANIFEST_PATH).unwrap();
+
+        (map, manifest)
+    }
+
+    #[test]
+    fn harvest_empties_the_nearest_deposit_first() {
+        let (mut map, manifest) = setup(&[
+            (12, 10, TileType::Forest),
+            (11, 10, TileType::Forest),
+            (11, 11, TileType::Rock),
+        ]);
+        let rect = Rect::new(10, 10, 1, 1);
+        assert_eq!(remaining(&map, &manifest, "wood", &rect, 2), 300);
+
+        let (taken, changed) = harvest(&mut map, &manifest, "wood", &rect, 2, 200, 100);
+        assert_eq!(taken, 200);
+        assert!(changed);
+        let near = map.xy_idx(11, 10);
+        let far = map.xy_idx(12, 10);
+        assert_eq!(map.tiles[near], TileType::Stumps);
+        assert_eq!(map.tiles[far], TileType::Forest);
+        assert_eq!(map.drawn[far], 50);
+        assert_eq!(remaining(&map, &manifest, "wood", &rect, 2), 100);
+        assert_eq!(remaining(&map, &manifest, "stone", &rect, 2), 400);
+    }
+
+    #[test]
+    fn harvest_stops_at_the_radius() {
+        let (mut map, manifest) = setup(&[(14, 10, TileType::Forest)]);
+        let rect = Rect::new(10, 10, 1, 1);
+
+        assert_eq!(
+            harvest(&mut map, &manifest, "wood", &rect, 2, 100, 100),
+            (0, false)
+        );
+    }
+
+    #[test]
+    fn felled_forest_grows_back_but_quarried_rock_does_not() {
+        let (mut map, manifest) = setup(&[(11, 10, TileType::Forest), (9, 10, TileType::Rock)]);
+        let rect = Rect::new(10, 10, 1, 1);
+        harvest(&mut map, &manifest, "wood", &rect, 1, 150, 100);
+        harvest(&mut map, &manifest, "stone", &rect, 1, 400, 100);
+        let forest = map.xy_idx(11, 10);
+        let rock = map.xy_idx(9, 10);
+        assert_eq!(map.tiles[rock], TileType::Floor);
+        assert_eq!(map.regrowing.len(), 1);
+
+        assert!(!regrow(&mut map, 100 + 899));
+        assert_eq!(map.tiles[forest], TileType::Stumps);
+        assert!(regrow(&mut map, 100 + 900));
+        assert_eq!(map.tiles[forest], TileType::Forest);
+        assert_eq!(map.drawn[forest], 0);
+        assert!(map.regrowing.is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use rltk::RGB;
+use specs::prelude::*;
+use std::cmp::max;
+use std::collections::HashSet;
+
+use crate::{utils, GameClock, PlayerStats, ResourceType};
+
+/// Oldest entries are dropped once the log grows past this.
+pub const MAX_LOG_ENTRIES: usize = 1000;
+
+/// What a log entry is about, which decides its colour.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum LogCategory {
+    Info,
+    Failure,      // an action that couldn't be carried out
+    Storage,      // a resource reached its cap
+    Construction, // buildings finished, upgraded or torn down
+    Progress,     // player level ups
+    Military,
+}
+
+impl LogCategory {
+    pub fn color(&self) -> RGB {
+        match self {
+            LogCategory::Info => RGB::named(rltk::WHITE),
+            LogCategory::Failure => *utils::MORANDI_RED,
+            LogCategory::Storage => RGB::named(rltk::CYAN),
+            LogCategory::Construction => RGB::named(rltk::GREEN),
+            LogCategory::Progress => RGB::named(rltk::GOLD),
+            LogCategory::Military => RGB::named(rltk::ORANGE_RED),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogCategory::Info => "info",
+            LogCategory::Failure => "failed",
+            LogCategory::Storage => "storage",
+            LogCategory::Construction => "building",
+            LogCategory::Progress => "level",
+            LogCategory::Military => "raid",
+        }
+    }
+}
+
+pub struct LogEntry {
+    pub time: i64, // second
+    pub category: LogCategory,
+    pub text: String,
+}
+
+impl LogEntry {
+    /// Game time between `founded_at` and the entry as days, hours, minutes
+    /// and seconds, e.g. "2:14:05:09".
+    pub fn time_label(&self, founded_at: i64) -> String {
+        let elapsed = max(self.time - founded_at, 0);
+        format!(
+            "{}:{:02}:{:02}:{:02}",
+            elapsed / 86400,
+            elapsed / 3600 % 24,
+            elapsed / 60 % 60,
+            elapsed % 60
+        )
+    }
+}
+
+/// Messages shown in the bottom box, oldest first.
+pub struct GameLog {
+    pub entries: Vec<LogEntry>,
+    /// Resources already reported full, so one hovering at its cap is only
+    /// reported again after it has drained a bit.
+    pub full_storage: HashSet<ResourceType>,
+}
+
+impl GameLog {
+    /// A fresh log, welcoming the player at game time `current`.
+    pub fn new(current: i64) -> Self {
+        let mut log = GameLog {
+            entries: Vec::new(),
+            full_storage: HashSet::new(),
+        };
+        log.add(current, LogCategory::Info, "Welcome to your settlement.");
+        log
+    }
+
+    pub fn add(&mut self, time: i64, category: LogCategory, text: impl Into<String>) {
+        self.entries.push(LogEntry {
+            time,
+            category,
+            text: text.into(),
+        });
+        if self.entries.len() > MAX_LOG_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+}
+
+/// When the player's settlement was founded, which log times count from.
+pub fn founded_at(ecs: &World) -> i64 {
+    let player = *ecs.fetch::<Entity>();
+    ecs.read_storage::<PlayerStats>()
+        .get(player)
+        .map_or(0, |stats| stats.founded_at)
+}
+
+/// Adds an entry stamped with the current game time.
+pub fn log(ecs: &World, category: LogCategory, text: impl Into<String>) {
+    let current = ecs.fetch::<GameClock>().now();
+    ecs.write_resource::<GameLog>().add(current, category, text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_label_counts_from_the_founding() {
+        let entry = LogEntry {
+            time: 1_000 + 2 * 86400 + 14 * 3600 + 5 * 60 + 9,
+            category: LogCategory::Info,
+            text: String::new(),
+        };
+        assert_eq!(entry.time_label(1_000), "2:14:05:09");
+        assert_eq!(entry.time_label(entry.time + 5), "0:00:00:00");
+    }
+}
@@ -1,20 +1,43 @@
-use rltk::{Console, Point, Rltk, VirtualKeyCode, RGB};
-use serde::de;
+use rltk::{Rltk, RGB};
 use specs::prelude::*;
 
-use crate::{utils, BuildingDetail, ConstructionManifest, MAP_COUNT, MAP_WIDTH};
+use crate::construction_system;
+use crate::control::MouseCursor;
+use crate::deposits;
+use crate::gamelog::{self, GameLog};
+use crate::keybindings::{key_name, Action, KeyBindings, KEY_BINDINGS_PATH};
+use crate::progression::LevelTable;
+use crate::resource_system;
+use crate::resource_system::{OfflineReport, OfflineYield};
+use crate::saveload_system::SaveNotice;
+use crate::{
+    saveload_system, utils, BuildingDetail, ConstructionManifest, GameClock, ManifestWatcher,
+    ResourceType, MAP_COUNT, MAP_WIDTH,
+};
 
 use super::{
-    components::*, Map, Rect, ResourceType, RunState, State, MAP_HEIGHT, MAP_PADDING_BOTTOM,
-    MAP_PADDING_LEFT, MAP_PADDING_UP, WINDOW_HEIGHT, WINDOW_WIDTH,
+    components::*, tile_glyph, Camera, Map, Rect, RunState, TileType, MAP_HEIGHT,
+    MAP_PADDING_BOTTOM, MAP_PADDING_LEFT, MAP_PADDING_UP, VIEW_HEIGHT, VIEW_WIDTH, WINDOW_HEIGHT,
+    WINDOW_WIDTH,
 };
 use std::cmp::{max, min};
+use std::collections::HashMap;
 
 pub const UIBOX_X: usize = 0;
-pub const UIBOX_Y: usize = MAP_PADDING_UP + MAP_HEIGHT + 1;
+pub const UIBOX_Y: usize = MAP_PADDING_UP + VIEW_HEIGHT + 1;
 pub const UIBOX_WIDTH: usize = WINDOW_WIDTH - 1;
-pub const UIBOX_HEIGHT: usize = WINDOW_HEIGHT - MAP_PADDING_UP - MAP_HEIGHT - 2;
-pub const CONSTRUCTION_INFO_WIDTH: usize = 20;
+pub const UIBOX_HEIGHT: usize = WINDOW_HEIGHT - MAP_PADDING_UP - VIEW_HEIGHT - 2;
+pub const MINIMAP_WIDTH: usize = 24;
+pub const MINIMAP_HEIGHT: usize = 12;
+pub const MINIMAP_X: usize = UIBOX_X + UIBOX_WIDTH - 52 - MINIMAP_WIDTH;
+pub const MINIMAP_Y: usize = UIBOX_Y + 1;
+/// The status column between the minimap and the key hints, and the hints
+/// along the right edge of the bottom box.
+const STATUS_X: usize = UIBOX_X + UIBOX_WIDTH - 50;
+const STATUS_WIDTH: usize = 29;
+const HINTS_X: usize = UIBOX_X + UIBOX_WIDTH - 20;
+const HINTS_WIDTH: usize = 19;
+pub const CONSTRUCTION_INFO_WIDTH: usize = 24;
 pub const CONSTRUCTION_INFO_HEIGHT: usize = 20;
 
 pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
@@ -32,49 +55,616 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     let player_stats = stats_storage.get(player).expect("Player must have stats");
 
     let runstate = *ecs.read_resource::<RunState>();
-    let food_stats;
-    let wood_stats;
-    let stone_stats;
-    if runstate == RunState::PreRun {
-        food_stats = format!("Food:  - / - (-/sec)");
-        wood_stats = format!("Wood:  - / - (-/sec)");
-        stone_stats = format!("Stone: - / - (-/sec)");
+    let manifest = ecs.fetch::<ConstructionManifest>();
+    let label_width = manifest
+        .resources
+        .iter()
+        .map(|resource| resource.name.len())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    for (idx, resource) in manifest.resources.iter().enumerate() {
+        let label = format!(
+            "{:width$}",
+            format!("{}:", resource.name),
+            width = label_width
+        );
+        let resource_stats = match player_stats.resources.get(&resource.id) {
+            Some(info) if runstate != RunState::PreRun => format!(
+                "{} {} / {} ({:+}/sec)",
+                label, info.amount, info.max_amount, info.rate
+            ),
+            _ => format!("{} - / - (-/sec)", label),
+        };
+
+        // four resources per column
+        ctx.print_color(
+            UIBOX_X + 1 + idx / 4 * 30,
+            UIBOX_Y + 1 + idx % 4,
+            utils::parse_color(&resource.fg).unwrap_or_else(|| RGB::named(rltk::YELLOW)),
+            RGB::named(rltk::BLACK),
+            resource_stats,
+        );
+    }
+
+    let level_info = if runstate == RunState::PreRun {
+        "Level: - (XP -)".to_string()
+    } else {
+        match ecs.fetch::<LevelTable>().next_threshold(player_stats.level) {
+            Some(next) => format!(
+                "Level: {} (XP {} / {})",
+                player_stats.level, player_stats.xp, next
+            ),
+            None => format!(
+                "Level: {} (XP {}, max)",
+                player_stats.level, player_stats.xp
+            ),
+        }
+    };
+    ctx.print_color(
+        STATUS_X,
+        UIBOX_Y + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        clipped(&level_info, STATUS_WIDTH),
+    );
+
+    let population_info = if runstate == RunState::PreRun {
+        "Citizens: - / -".to_string()
     } else {
-        food_stats = format!(
-            "Food:  {} / {} (+{}/sec)",
-            player_stats.food.amount, player_stats.food.max_amount, player_stats.food.rate
+        let workforces = ecs.read_storage::<Workforce>();
+        format!(
+            "Citizens: {} / {} ({} idle)",
+            player_stats.population,
+            player_stats.housing,
+            utils::idle_workers(player_stats, &workforces)
+        )
+    };
+    ctx.print_color(
+        STATUS_X,
+        UIBOX_Y + 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        clipped(&population_info, STATUS_WIDTH),
+    );
+
+    // starvation warning, over two rows to fit the column
+    if runstate != RunState::PreRun && player_stats.starving_seconds > 0 {
+        let population = &manifest.population;
+        let leaving_info = if player_stats.starving_seconds < population.starvation_grace_seconds {
+            format!(
+                "Citizens leave in {}s",
+                population.starvation_grace_seconds - player_stats.starving_seconds
+            )
+        } else {
+            "Citizens are leaving".to_string()
+        };
+        let starving_info = format!("STARVING: output {}%", population.starvation_penalty);
+        for (offset, info) in [starving_info, leaving_info].iter().enumerate() {
+            ctx.print_color(
+                STATUS_X,
+                UIBOX_Y + 4 + offset,
+                RGB::named(rltk::RED),
+                RGB::named(rltk::BLACK),
+                clipped(info, STATUS_WIDTH),
+            );
+        }
+    }
+
+    // garrison and raids
+    if runstate != RunState::PreRun {
+        let garrisons = ecs.read_storage::<Garrison>();
+        let soldiers: i32 = garrisons.join().map(|garrison| garrison.soldiers).sum();
+        let raid_info = match ecs.read_storage::<RaidSchedule>().get(player) {
+            Some(schedule) => format!(
+                "Soldiers: {}, raid in {}s",
+                soldiers,
+                (schedule.next_raid - ecs.fetch::<GameClock>().now()).max(0)
+            ),
+            None => format!("Soldiers: {}", soldiers),
+        };
+        ctx.print_color(
+            STATUS_X,
+            UIBOX_Y + 3,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            clipped(&raid_info, STATUS_WIDTH),
         );
-        wood_stats = format!(
-            "Wood:  {} / {} (+{}/sec)",
-            player_stats.wood.amount, player_stats.wood.max_amount, player_stats.wood.rate
+    }
+
+    if let Some(notice) = &ecs.fetch::<ManifestWatcher>().notice {
+        ctx.print_color(
+            UIBOX_X + 1,
+            UIBOX_Y + 5,
+            *utils::MORANDI_RED,
+            RGB::named(rltk::BLACK),
+            clipped(notice, MINIMAP_X - UIBOX_X - 2),
         );
-        stone_stats = format!(
-            "Stone: {} / {} (+{}/sec)",
-            player_stats.stone.amount, player_stats.stone.max_amount, player_stats.stone.rate
+    }
+
+    // message log, newest last
+    let log = ecs.fetch::<GameLog>();
+    let founded_at = gamelog::founded_at(ecs);
+    let log_lines = UIBOX_HEIGHT - 6;
+    for (offset, entry) in log
+        .entries
+        .iter()
+        .skip(log.entries.len().saturating_sub(log_lines))
+        .enumerate()
+    {
+        let line = format!("{} {}", entry.time_label(founded_at), entry.text);
+        let line = clipped(&line, MINIMAP_X - UIBOX_X - 2);
+        ctx.print_color(
+            UIBOX_X + 1,
+            UIBOX_Y + 6 + offset,
+            entry.category.color(),
+            RGB::named(rltk::BLACK),
+            line,
         );
     }
 
+    draw_minimap(ecs, ctx);
+
+    let clock = ecs.fetch::<GameClock>();
+    let speed_info = if clock.is_paused() {
+        "PAUSED".to_string()
+    } else {
+        format!("Speed: {}x", clock.speed())
+    };
     ctx.print_color(
-        UIBOX_X + 1,
+        HINTS_X,
         UIBOX_Y + 1,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        &food_stats,
+        clipped(&speed_info, HINTS_WIDTH),
     );
+    let bindings = ecs.fetch::<KeyBindings>();
     ctx.print_color(
-        UIBOX_X + 1,
+        HINTS_X,
         UIBOX_Y + 2,
-        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::GRAY60),
         RGB::named(rltk::BLACK),
-        &wood_stats,
+        clipped(
+            &format!("[{}] pause", bindings.label(Action::Pause)),
+            HINTS_WIDTH,
+        ),
     );
     ctx.print_color(
-        UIBOX_X + 1,
+        HINTS_X,
         UIBOX_Y + 3,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        clipped(
+            &format!(
+                "[{}..{}] 1x/2x/5x/10x",
+                bindings.label(Action::Speed1),
+                bindings.label(Action::Speed4)
+            ),
+            HINTS_WIDTH,
+        ),
+    );
+    ctx.print_color(
+        HINTS_X,
+        UIBOX_Y + 4,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        clipped(
+            &format!("[{}] key bindings", bindings.label(Action::ShowBindings)),
+            HINTS_WIDTH,
+        ),
+    );
+    ctx.print_color(
+        HINTS_X,
+        UIBOX_Y + 5,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        clipped(
+            &format!(
+                "[{}] build queue ({})",
+                bindings.label(Action::ShowBuildQueue),
+                ecs.read_storage::<ConstructionSite>().join().count()
+            ),
+            HINTS_WIDTH,
+        ),
+    );
+    ctx.print_color(
+        HINTS_X,
+        UIBOX_Y + 6,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        clipped(
+            &format!("[{}] message log", bindings.label(Action::ShowLog)),
+            HINTS_WIDTH,
+        ),
+    );
+    ctx.print_color(
+        HINTS_X,
+        UIBOX_Y + 7,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        clipped(
+            &format!("[{}] statistics", bindings.label(Action::ShowStatistics)),
+            HINTS_WIDTH,
+        ),
+    );
+}
+
+/// `text` cut down to `width` characters.
+fn clipped(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Every minimap cell shows two samples stacked with a half block, so the
+/// minimap has twice as many sample rows as it has cells.
+const MINIMAP_SAMPLE_ROWS: usize = MINIMAP_HEIGHT * 2;
+
+/// World tiles covered by one minimap sample, horizontally and vertically.
+fn minimap_scale() -> (i32, i32) {
+    (
+        (MAP_WIDTH as i32 + MINIMAP_WIDTH as i32 - 1) / MINIMAP_WIDTH as i32,
+        (MAP_HEIGHT as i32 + MINIMAP_SAMPLE_ROWS as i32 - 1) / MINIMAP_SAMPLE_ROWS as i32,
+    )
+}
+
+/// The minimap sample a world tile falls into.
+fn minimap_sample(x: i32, y: i32) -> Option<usize> {
+    let (scale_x, scale_y) = minimap_scale();
+    let (sample_x, sample_y) = (x / scale_x, y / scale_y);
+    if x < 0 || y < 0 || sample_x >= MINIMAP_WIDTH as i32 || sample_y >= MINIMAP_SAMPLE_ROWS as i32
+    {
+        return None;
+    }
+
+    Some(sample_y as usize * MINIMAP_WIDTH + sample_x as usize)
+}
+
+/// World tile shown under a screen cell of the minimap, if it is on it.
+pub fn minimap_to_world(screen_x: i32, screen_y: i32) -> Option<(i32, i32)> {
+    let x = screen_x - MINIMAP_X as i32;
+    let y = screen_y - MINIMAP_Y as i32;
+    if x < 0 || y < 0 || x >= MINIMAP_WIDTH as i32 || y >= MINIMAP_HEIGHT as i32 {
+        return None;
+    }
+
+    let (scale_x, scale_y) = minimap_scale();
+    Some((
+        min(x * scale_x + scale_x / 2, MAP_WIDTH as i32 - 1),
+        min(y * 2 * scale_y + scale_y, MAP_HEIGHT as i32 - 1),
+    ))
+}
+
+/// Downsampled map with buildings, the camera viewport and the cursor.
+fn draw_minimap(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let camera = ecs.fetch::<Camera>();
+    let (scale_x, scale_y) = minimap_scale();
+
+    // the most common tile among a few points of every sample
+    let mut samples = vec![RGB::named(rltk::BLACK); MINIMAP_WIDTH * MINIMAP_SAMPLE_ROWS];
+    for (idx, sample) in samples.iter_mut().enumerate() {
+        let x1 = (idx % MINIMAP_WIDTH) as i32 * scale_x;
+        let y1 = (idx / MINIMAP_WIDTH) as i32 * scale_y;
+        let mut counts: Vec<(TileType, i32)> = Vec::new();
+        for step_x in 0..3 {
+            for step_y in 0..3 {
+                let x = x1 + scale_x * (2 * step_x + 1) / 6;
+                let y = y1 + scale_y * (2 * step_y + 1) / 6;
+                if x >= map.width || y >= map.height {
+                    continue;
+                }
+                let tile = map.tiles[map.xy_idx(x, y)];
+                match counts.iter_mut().find(|(counted, _)| *counted == tile) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((tile, 1)),
+                }
+            }
+        }
+        if let Some((tile, _)) = counts.iter().max_by_key(|(_, count)| *count) {
+            *sample = minimap_color(*tile);
+        }
+    }
+
+    // buildings
+    let buildings = ecs.read_storage::<Building>();
+    let renderables = ecs.read_storage::<Renderable>();
+    for (building, renderable) in (&buildings, &renderables).join() {
+        let rect = &building.rect;
+        for y in (rect.y1..rect.y2).step_by(scale_y as usize) {
+            for x in (rect.x1..rect.x2).step_by(scale_x as usize) {
+                if let Some(idx) = minimap_sample(x, y) {
+                    samples[idx] = renderable.fg;
+                }
+            }
+        }
+        if let Some(idx) = minimap_sample(rect.x2 - 1, rect.y2 - 1) {
+            samples[idx] = renderable.fg;
+        }
+    }
+
+    // viewport outline
+    let view_x2 = camera.x + VIEW_WIDTH as i32 - 1;
+    let view_y2 = camera.y + VIEW_HEIGHT as i32 - 1;
+    for x in (camera.x..=view_x2)
+        .step_by(scale_x as usize)
+        .chain([view_x2])
+    {
+        for y in [camera.y, view_y2] {
+            if let Some(idx) = minimap_sample(x, y) {
+                samples[idx] = RGB::named(rltk::WHITE);
+            }
+        }
+    }
+    for y in (camera.y..=view_y2)
+        .step_by(scale_y as usize)
+        .chain([view_y2])
+    {
+        for x in [camera.x, view_x2] {
+            if let Some(idx) = minimap_sample(x, y) {
+                samples[idx] = RGB::named(rltk::WHITE);
+            }
+        }
+    }
+
+    // cursor
+    let cursor = match *ecs.fetch::<RunState>() {
+        RunState::ConstructionSpotSelecting { x, y, .. }
+        | RunState::ConstructionSelecting { x, y }
+        | RunState::ConfirmTearDown { x, y } => Some((x, y)),
+        _ => None,
+    };
+    if let Some(idx) = cursor.and_then(|(x, y)| minimap_sample(x, y)) {
+        samples[idx] = RGB::named(rltk::YELLOW);
+    }
+
+    for y in 0..MINIMAP_HEIGHT {
+        for x in 0..MINIMAP_WIDTH {
+            ctx.set(
+                MINIMAP_X + x,
+                MINIMAP_Y + y,
+                samples[y * 2 * MINIMAP_WIDTH + x],
+                samples[(y * 2 + 1) * MINIMAP_WIDTH + x],
+                rltk::to_cp437('▀'),
+            );
+        }
+    }
+}
+
+fn minimap_color(tile: TileType) -> RGB {
+    match tile {
+        TileType::Floor => RGB::from_f32(0.1, 0.15, 0.1),
+        _ => tile_glyph(tile).1,
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuSelection {
+    NewGame,
+    LoadGame,
+    Quit,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuResult {
+    NoSelection { selected: MainMenuSelection },
+    Selected { selected: MainMenuSelection },
+}
+
+pub fn draw_main_menu(ecs: &mut World, ctx: &mut Rltk) -> MainMenuResult {
+    let save_exists = saveload_system::does_save_exist();
+    let runstate = *ecs.fetch::<RunState>();
+
+    ctx.print_color_centered(
+        WINDOW_HEIGHT / 2 - 5,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Aurorian",
+    );
+
+    if let RunState::MainMenu {
+        selected: selection,
+    } = runstate
+    {
+        let mut options = vec![("Begin New Game", MainMenuSelection::NewGame)];
+        if save_exists {
+            options.push(("Load Game", MainMenuSelection::LoadGame));
+        }
+        options.push(("Quit", MainMenuSelection::Quit));
+
+        for (offset, (label, option)) in options.iter().enumerate() {
+            let color = if *option == selection {
+                RGB::named(rltk::MAGENTA)
+            } else {
+                RGB::named(rltk::WHITE)
+            };
+            ctx.print_color_centered(
+                WINDOW_HEIGHT / 2 - 2 + offset,
+                color,
+                RGB::named(rltk::BLACK),
+                label,
+            );
+        }
+
+        if let Some(notice) = &ecs.fetch::<SaveNotice>().notice {
+            let notice: String = notice.chars().take(WINDOW_WIDTH - 2).collect();
+            ctx.print_color_centered(
+                WINDOW_HEIGHT / 2 + 2 + options.len(),
+                *utils::MORANDI_RED,
+                RGB::named(rltk::BLACK),
+                &notice,
+            );
+        }
+
+        let current = options
+            .iter()
+            .position(|(_, option)| *option == selection)
+            .unwrap_or(0);
+
+        // mouse
+        let hovered = options.iter().enumerate().find(|(offset, (label, _))| {
+            mouse_over(
+                ctx,
+                (WINDOW_WIDTH - label.len()) as i32 / 2,
+                (WINDOW_HEIGHT / 2 - 2 + offset) as i32,
+                label.len() as i32,
+            )
+        });
+        if let Some((_, (_, option))) = hovered {
+            if ctx.left_click {
+                return MainMenuResult::Selected { selected: *option };
+            }
+            if ecs.fetch::<MouseCursor>().moved {
+                return MainMenuResult::NoSelection { selected: *option };
+            }
+        }
+
+        // control
+        let action = ecs.fetch::<KeyBindings>().action(
+            ctx.key,
+            &[Action::CursorUp, Action::CursorDown, Action::Confirm],
+        );
+        match action {
+            None => {
+                return MainMenuResult::NoSelection {
+                    selected: selection,
+                }
+            }
+            Some(action) => match action {
+                Action::CursorUp => {
+                    let idx = if current == 0 {
+                        options.len() - 1
+                    } else {
+                        current - 1
+                    };
+                    return MainMenuResult::NoSelection {
+                        selected: options[idx].1,
+                    };
+                }
+                Action::CursorDown => {
+                    let idx = (current + 1) % options.len();
+                    return MainMenuResult::NoSelection {
+                        selected: options[idx].1,
+                    };
+                }
+                Action::Confirm => {
+                    return MainMenuResult::Selected {
+                        selected: options[current].1,
+                    }
+                }
+                _ => {
+                    return MainMenuResult::NoSelection {
+                        selected: selection,
+                    }
+                }
+            },
+        }
+    }
+
+    MainMenuResult::NoSelection {
+        selected: MainMenuSelection::NewGame,
+    }
+}
+
+pub const OFFLINE_SUMMARY_WIDTH: usize = 50;
+pub const OFFLINE_SUMMARY_HEIGHT: usize = 10;
+pub const OFFLINE_SUMMARY_X: usize = (WINDOW_WIDTH - OFFLINE_SUMMARY_WIDTH) / 2;
+pub const OFFLINE_SUMMARY_Y: usize = (WINDOW_HEIGHT - OFFLINE_SUMMARY_HEIGHT) / 2;
+#[derive(PartialEq, Copy, Clone)]
+pub enum OfflineSummaryResult {
+    NoResponse,
+    Dismiss,
+}
+
+pub fn draw_offline_summary(ecs: &mut World, ctx: &mut Rltk) -> OfflineSummaryResult {
+    let report = ecs.fetch::<OfflineReport>();
+
+    ctx.draw_box(
+        OFFLINE_SUMMARY_X,
+        OFFLINE_SUMMARY_Y,
+        OFFLINE_SUMMARY_WIDTH,
+        OFFLINE_SUMMARY_HEIGHT,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        OFFLINE_SUMMARY_X + 1,
+        OFFLINE_SUMMARY_Y,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "While you were away",
+    );
+    ctx.print_color(
+        OFFLINE_SUMMARY_X + 2,
+        OFFLINE_SUMMARY_Y + 2,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        format!(
+            "{}h {:02}m {:02}s passed",
+            report.elapsed / 3600,
+            report.elapsed % 3600 / 60,
+            report.elapsed % 60
+        ),
+    );
+
+    let manifest = ecs.fetch::<ConstructionManifest>();
+    let lines: Vec<(String, OfflineYield)> = manifest
+        .resources
+        .iter()
+        .map(|resource| {
+            let produced = report.yields.get(&resource.id).copied().unwrap_or_default();
+            (format!("{}:", resource.name), produced)
+        })
+        .collect();
+    for (offset, (label, produced)) in lines.iter().enumerate() {
+        ctx.print_color(
+            OFFLINE_SUMMARY_X + 2,
+            OFFLINE_SUMMARY_Y + 4 + offset,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            if produced.consumed > 0 {
+                format!("{} +{} -{}", label, produced.produced, produced.consumed)
+            } else {
+                format!("{} +{}", label, produced.produced)
+            },
+        );
+        if produced.wasted > 0 {
+            ctx.print_color(
+                OFFLINE_SUMMARY_X + 26,
+                OFFLINE_SUMMARY_Y + 4 + offset,
+                *utils::MORANDI_RED,
+                RGB::named(rltk::BLACK),
+                format!("{} wasted at the cap", produced.wasted),
+            );
+        }
+    }
+
+    ctx.print_color(
+        OFFLINE_SUMMARY_X + 1,
+        OFFLINE_SUMMARY_Y + OFFLINE_SUMMARY_HEIGHT,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        &stone_stats,
+        format!(
+            "[{}] or click to continue",
+            ecs.fetch::<KeyBindings>().label(Action::Confirm)
+        ),
     );
+
+    // control
+    if ctx.left_click {
+        return OfflineSummaryResult::Dismiss;
+    }
+    let action = ecs
+        .fetch::<KeyBindings>()
+        .action(ctx.key, &[Action::Confirm, Action::Cancel]);
+    match action {
+        Some(_) => OfflineSummaryResult::Dismiss,
+        None => OfflineSummaryResult::NoResponse,
+    }
+}
+
+/// Whether the mouse is over the `width` cells right of `x` on row `y`.
+fn mouse_over(ctx: &Rltk, x: i32, y: i32, width: i32) -> bool {
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    mouse_y == y && mouse_x >= x && mouse_x < x + width
 }
 
 pub const CONSTRUCTION_MENU_X: usize = 15;
@@ -86,6 +676,7 @@ pub enum ConstructionMenuResult {
     Escape,
     NoSelection { selected_idx: usize },
     Selected { selected_idx: usize },
+    Unaffordable { selected_idx: usize },
 }
 
 pub fn draw_construction_menu(ecs: &mut World, ctx: &mut Rltk) -> ConstructionMenuResult {
@@ -126,18 +717,18 @@ pub fn draw_construction_menu(ecs: &mut World, ctx: &mut Rltk) -> ConstructionMe
             CONSTRUCTION_MENU_Y + CONSTRUCTION_MENU_HEIGHT,
             RGB::named(rltk::YELLOW),
             RGB::named(rltk::BLACK),
-            "ESCAPE to cancel",
+            format!(
+                "[{}] to cancel",
+                ecs.fetch::<KeyBindings>().label(Action::Cancel)
+            ),
         );
 
         if construction_manifest.buildings.is_empty() {
             // control
-            match ctx.key {
-                None => return ConstructionMenuResult::NoSelection { selected_idx },
-                Some(key) => match key {
-                    VirtualKeyCode::Escape => return ConstructionMenuResult::Escape,
-                    _ => return ConstructionMenuResult::NoSelection { selected_idx },
-                },
+            if ecs.fetch::<KeyBindings>().pressed(ctx.key, Action::Cancel) {
+                return ConstructionMenuResult::Escape;
             }
+            return ConstructionMenuResult::NoSelection { selected_idx };
         }
 
         // draw construction options
@@ -154,6 +745,7 @@ pub fn draw_construction_menu(ecs: &mut World, ctx: &mut Rltk) -> ConstructionMe
                 print_building_requirements(
                     ctx,
                     player_stats,
+                    &construction_manifest,
                     detail,
                     0,
                     separate_vertical_line_x + 1,
@@ -170,22 +762,72 @@ pub fn draw_construction_menu(ecs: &mut World, ctx: &mut Rltk) -> ConstructionMe
             }
         }
 
+        // mouse
+        let hovered = (0..construction_manifest.buildings.len()).find(|idx| {
+            mouse_over(
+                ctx,
+                CONSTRUCTION_MENU_X as i32 + 2,
+                (CONSTRUCTION_MENU_Y + 2 + idx) as i32,
+                separate_vertical_line_x - CONSTRUCTION_MENU_X as i32 - 2,
+            )
+        });
+        if let Some(idx) = hovered {
+            if ctx.left_click {
+                let detail = &construction_manifest.buildings[idx];
+                if utils::requirements_check(player_stats, None, detail, 0, &construction_manifest)
+                {
+                    return ConstructionMenuResult::Selected { selected_idx: idx };
+                }
+                return ConstructionMenuResult::Unaffordable { selected_idx: idx };
+            }
+            if ecs.fetch::<MouseCursor>().moved {
+                return ConstructionMenuResult::NoSelection { selected_idx: idx };
+            }
+        } else if ctx.left_click {
+            let (mouse_x, mouse_y) = ctx.mouse_pos();
+            let menu = Rect::new(
+                CONSTRUCTION_MENU_X as i32,
+                CONSTRUCTION_MENU_Y as i32,
+                CONSTRUCTION_MENU_WIDTH as i32 + 1,
+                CONSTRUCTION_MENU_HEIGHT as i32 + 1,
+            );
+            // clicking outside the menu closes it
+            if !menu.contains(mouse_x, mouse_y) {
+                return ConstructionMenuResult::Escape;
+            }
+        }
+
         // control
         let construction_manifest = ecs.fetch::<ConstructionManifest>();
         let detail = &construction_manifest.buildings[selected_idx];
-        match ctx.key {
+        let action = ecs.fetch::<KeyBindings>().action(
+            ctx.key,
+            &[
+                Action::Cancel,
+                Action::Confirm,
+                Action::CursorUp,
+                Action::CursorDown,
+            ],
+        );
+        match action {
             None => return ConstructionMenuResult::NoSelection { selected_idx },
-            Some(key) => match key {
-                VirtualKeyCode::Escape => return ConstructionMenuResult::Escape,
-                VirtualKeyCode::Return => {
+            Some(action) => match action {
+                Action::Cancel => return ConstructionMenuResult::Escape,
+                Action::Confirm => {
                     // #TODO requirements check
-                    if utils::requirements_check(player_stats, None, detail, 0) {
+                    if utils::requirements_check(
+                        player_stats,
+                        None,
+                        detail,
+                        0,
+                        &construction_manifest,
+                    ) {
                         return ConstructionMenuResult::Selected { selected_idx };
                     }
 
-                    return ConstructionMenuResult::NoSelection { selected_idx };
+                    return ConstructionMenuResult::Unaffordable { selected_idx };
                 }
-                VirtualKeyCode::K => {
+                Action::CursorUp => {
                     if selected_idx == 0 {
                         selected_idx = construction_manifest.buildings.len() - 1;
                     } else {
@@ -193,7 +835,7 @@ pub fn draw_construction_menu(ecs: &mut World, ctx: &mut Rltk) -> ConstructionMe
                     }
                     return ConstructionMenuResult::NoSelection { selected_idx };
                 }
-                VirtualKeyCode::J => {
+                Action::CursorDown => {
                     selected_idx = (selected_idx + 1) % construction_manifest.buildings.len();
                     return ConstructionMenuResult::NoSelection { selected_idx };
                 }
@@ -218,15 +860,24 @@ pub fn draw_construction_spot(ecs: &mut World, ctx: &mut Rltk) -> ConstructionSp
     if let RunState::ConstructionSpotSelecting { selected_idx, x, y } = runstate {
         let detail = &ecs.fetch::<ConstructionManifest>().buildings[selected_idx];
 
-        let mut valid = true;
         let target_spot = Rect::new(x, y, detail.width, detail.height);
+        let map = ecs.fetch::<Map>();
+        let camera = ecs.fetch::<Camera>();
+        let mut problem = utils::placement_problem(&map, detail, &target_spot);
         let buildings_storage = ecs.read_storage::<Building>();
         for building in buildings_storage.join() {
             if target_spot.intersect(&building.rect) {
-                valid = false;
+                problem = Some("Overlaps another building".to_string());
                 break;
             }
         }
+        for site in ecs.read_storage::<ConstructionSite>().join() {
+            if target_spot.intersect(&site.rect) {
+                problem = Some("Overlaps a construction site".to_string());
+                break;
+            }
+        }
+        let valid = problem.is_none();
 
         // draw the spot
         let spot_color = if valid {
@@ -236,16 +887,95 @@ pub fn draw_construction_spot(ecs: &mut World, ctx: &mut Rltk) -> ConstructionSp
         };
         for i in x..x + detail.width {
             for j in y..y + detail.height {
-                ctx.set_bg(i, j, spot_color);
+                if let Some((screen_x, screen_y)) = camera.to_screen(i, j) {
+                    ctx.set_bg(screen_x, screen_y, spot_color);
+                }
+            }
+        }
+
+        // why the spot is invalid, or what the building would yield there
+        let preview = match &problem {
+            Some(problem) => Some((problem.clone(), RGB::named(rltk::RED))),
+            None => match (&detail.resource_type, detail.levels[&0].rate) {
+                (Some(resource_type), Some(rate)) => {
+                    let manifest = ecs.fetch::<ConstructionManifest>();
+                    let yield_percent = utils::terrain_yield(&map, detail, &target_spot);
+                    let mut info = format!(
+                        "{}: +{}/sec ({}% yield)",
+                        manifest.resource_name(resource_type),
+                        rate * yield_percent / 100,
+                        yield_percent
+                    );
+                    if let Some(radius) = detail.harvest_radius {
+                        let left = deposits::remaining(
+                            &map,
+                            &manifest,
+                            resource_type,
+                            &target_spot,
+                            radius,
+                        );
+                        info = format!("{}, {} in range", info, left);
+                    }
+                    Some((info, RGB::named(rltk::GREEN)))
+                }
+                _ => None,
+            },
+        };
+        if let Some((text, color)) = preview {
+            let (screen_x, screen_y) = camera.screen_pos(x, y);
+            let text_y = if screen_y > MAP_PADDING_UP as i32 {
+                screen_y - 1
+            } else {
+                screen_y + detail.height
+            };
+            let text_x = min(
+                screen_x,
+                (MAP_PADDING_LEFT + VIEW_WIDTH) as i32 - text.chars().count() as i32,
+            );
+            ctx.print_color(
+                max(text_x, MAP_PADDING_LEFT as i32),
+                text_y,
+                color,
+                RGB::named(rltk::BLACK),
+                text,
+            );
+        }
+
+        // mouse: the spot follows the cursor, a click places the building
+        let (mouse_x, mouse_y) = ctx.mouse_pos();
+        if let Some((tile_x, tile_y)) = camera.to_world(mouse_x, mouse_y) {
+            if ctx.left_click {
+                if valid && target_spot.contains(tile_x, tile_y) {
+                    return ConstructionSpotSelectingResult::Selected { selected_idx, x, y };
+                }
+                return ConstructionSpotSelectingResult::NoSelection { selected_idx, x, y };
+            }
+            if ecs.fetch::<MouseCursor>().moved {
+                return ConstructionSpotSelectingResult::NoSelection {
+                    selected_idx,
+                    x: (tile_x - detail.width / 2).clamp(0, MAP_WIDTH as i32 - detail.width),
+                    y: (tile_y - detail.height / 2).clamp(0, MAP_HEIGHT as i32 - detail.height),
+                };
             }
         }
 
         // control
-        match ctx.key {
+        let action = ecs.fetch::<KeyBindings>().action(
+            ctx.key,
+            &[
+                Action::Cancel,
+                Action::Confirm,
+                Action::CursorUp,
+                Action::CursorDown,
+                Action::CursorLeft,
+                Action::CursorRight,
+            ],
+        );
+        match action {
             None => return ConstructionSpotSelectingResult::NoSelection { selected_idx, x, y },
-            Some(key) => match key {
-                VirtualKeyCode::Escape => return ConstructionSpotSelectingResult::Escape,
-                VirtualKeyCode::Return => {
+            Some(action) => match action {
+                Action::Cancel => return ConstructionSpotSelectingResult::Escape,
+                Action::Confirm => {
                     if !valid {
                         return ConstructionSpotSelectingResult::NoSelection { selected_idx, x, y };
                     }
@@ -257,37 +987,31 @@ pub fn draw_construction_spot(ecs: &mut World, ctx: &mut Rltk) -> ConstructionSp
 
                     return ConstructionSpotSelectingResult::Selected { selected_idx, x, y };
                 }
-                VirtualKeyCode::K => {
+                Action::CursorUp => {
                     return ConstructionSpotSelectingResult::NoSelection {
                         selected_idx,
                         x,
-                        y: max(y - 1, MAP_PADDING_UP as i32),
+                        y: max(y - 1, 0),
                     }
                 }
-                VirtualKeyCode::J => {
+                Action::CursorDown => {
                     return ConstructionSpotSelectingResult::NoSelection {
                         selected_idx,
                         x,
-                        y: min(
-                            y + 1,
-                            MAP_PADDING_UP as i32 + MAP_HEIGHT as i32 - detail.height,
-                        ),
+                        y: min(y + 1, MAP_HEIGHT as i32 - detail.height),
                     }
                 }
-                VirtualKeyCode::H => {
+                Action::CursorLeft => {
                     return ConstructionSpotSelectingResult::NoSelection {
                         selected_idx,
-                        x: max(x - 1, MAP_PADDING_LEFT as i32),
+                        x: max(x - 1, 0),
                         y,
                     }
                 }
-                VirtualKeyCode::L => {
+                Action::CursorRight => {
                     return ConstructionSpotSelectingResult::NoSelection {
                         selected_idx,
-                        x: min(
-                            x + 1,
-                            MAP_PADDING_LEFT as i32 + MAP_WIDTH as i32 - detail.width,
-                        ),
+                        x: min(x + 1, MAP_WIDTH as i32 - detail.width),
                         y,
                     }
                 }
@@ -299,10 +1023,14 @@ pub fn draw_construction_spot(ecs: &mut World, ctx: &mut Rltk) -> ConstructionSp
     ConstructionSpotSelectingResult::Escape
 }
 
+#[derive(PartialEq, Copy, Clone)]
 pub enum ConstructionSelectingResult {
     Escape,
     NoSelection { x: i32, y: i32 },
-    Selected { selected_idx: usize, x: i32, y: i32 },
+    Upgrade { x: i32, y: i32 },
+    FinishNow { x: i32, y: i32 },
+    TearDown { x: i32, y: i32 },
+    AssignWorker { x: i32, y: i32, delta: i32 },
 }
 
 pub fn draw_construction_selecting(ecs: &mut World, ctx: &mut Rltk) -> ConstructionSelectingResult {
@@ -324,47 +1052,88 @@ pub fn draw_construction_selecting(ecs: &mut World, ctx: &mut Rltk) -> Construct
             (x, y) = map.idx_xy(idx);
         }
 
-        let mut building_storage = ecs.write_storage::<Building>();
+        let building_storage = ecs.read_storage::<Building>();
         let name_storage = ecs.read_storage::<Name>();
-        let mut generator_storage = ecs.write_storage::<Generator>();
         let entities = ecs.entities();
         let building_manifest = ecs.fetch::<ConstructionManifest>();
         let player = *ecs.fetch::<Entity>();
-        let mut stats_storage = ecs.write_storage::<PlayerStats>();
-        let player_stats = stats_storage.get_mut(player).unwrap();
-        for (building, name, entity) in (&mut building_storage, &name_storage, &entities).join() {
+        let stats_storage = ecs.read_storage::<PlayerStats>();
+        let player_stats = stats_storage.get(player).unwrap();
+        let camera = ecs.fetch::<Camera>();
+        let mut clicked = None;
+        for (building, name, entity) in (&building_storage, &name_storage, &entities).join() {
             if x == building.rect.x1 && y == building.rect.y1 {
                 // draw the spot
                 for i in building.rect.y1..building.rect.y2 {
                     for j in building.rect.x1..building.rect.x2 {
-                        ctx.set_bg(j, i, RGB::named(rltk::SKY_BLUE));
+                        if let Some((screen_x, screen_y)) = camera.to_screen(j, i) {
+                            ctx.set_bg(screen_x, screen_y, RGB::named(rltk::SKY_BLUE));
+                        }
                     }
                 }
 
                 // draw building info and action menu
-                let mut detail = None;
-                for b in &building_manifest.buildings {
-                    if b.name == name.name {
-                        detail = Some(b);
-                    }
-                }
-                let generator = generator_storage.get_mut(entity);
-                draw_construction_info(
+                clicked = draw_construction_info(
                     ctx,
+                    ecs,
+                    entity,
                     player_stats,
                     building,
                     name,
-                    generator,
-                    detail.expect("Building must have detail"),
+                    &building_manifest,
                 );
             }
         }
 
+        // mouse: clicking another building selects it, open ground closes the panel
+        if let Some(result) = clicked {
+            return result;
+        }
+        if ctx.left_click {
+            let (mouse_x, mouse_y) = ctx.mouse_pos();
+            if let Some((tile_x, tile_y)) = camera.to_world(mouse_x, mouse_y) {
+                return match utils::building_under(ecs, tile_x, tile_y) {
+                    Some(entity) => {
+                        let rect = building_storage.get(entity).unwrap().rect;
+                        ConstructionSelectingResult::NoSelection {
+                            x: rect.x1,
+                            y: rect.y1,
+                        }
+                    }
+                    None => ConstructionSelectingResult::Escape,
+                };
+            }
+        }
+
         // control
-        match ctx.key {
+        let action = ecs.fetch::<KeyBindings>().action(
+            ctx.key,
+            &[
+                Action::Cancel,
+                Action::Upgrade,
+                Action::FinishNow,
+                Action::TearDown,
+                Action::AddWorker,
+                Action::RemoveWorker,
+                Action::CursorUp,
+                Action::CursorDown,
+                Action::CursorLeft,
+                Action::CursorRight,
+            ],
+        );
+        match action {
             None => return ConstructionSelectingResult::NoSelection { x, y },
-            Some(key) => match key {
-                VirtualKeyCode::Escape => return ConstructionSelectingResult::Escape,
+            Some(action) => match action {
+                Action::Cancel => return ConstructionSelectingResult::Escape,
+                Action::Upgrade => return ConstructionSelectingResult::Upgrade { x, y },
+                Action::FinishNow => return ConstructionSelectingResult::FinishNow { x, y },
+                Action::TearDown => return ConstructionSelectingResult::TearDown { x, y },
+                Action::AddWorker => {
+                    return ConstructionSelectingResult::AssignWorker { x, y, delta: 1 }
+                }
+                Action::RemoveWorker => {
+                    return ConstructionSelectingResult::AssignWorker { x, y, delta: -1 }
+                }
                 //  VirtualKeyCode::Return => {
                 //      for i in building.rect.y1..building.rect.y2 + 1 {
                 //          for j in building.rect.x1..building.rect.x2 + 1 {
@@ -374,8 +1143,8 @@ pub fn draw_construction_selecting(ecs: &mut World, ctx: &mut Rltk) -> Construct
 
                 //      return ConstructionSpotSelectingResult::Selected { selected_idx, x, y };
                 //  }
-                VirtualKeyCode::K => {
-                    if y == MAP_PADDING_UP as i32 {
+                Action::CursorUp => {
+                    if y == 0 {
                         return ConstructionSelectingResult::NoSelection { x, y };
                     }
 
@@ -396,13 +1165,13 @@ pub fn draw_construction_selecting(ecs: &mut World, ctx: &mut Rltk) -> Construct
 
                     return ConstructionSelectingResult::NoSelection { x, y };
                 }
-                VirtualKeyCode::J => {
+                Action::CursorDown => {
                     let map = ecs.fetch::<Map>();
-                    if y == (MAP_PADDING_UP + MAP_HEIGHT - 1) as i32 {
+                    if y == MAP_HEIGHT as i32 - 1 {
                         return ConstructionSelectingResult::NoSelection { x, y };
                     }
 
-                    let mut idx = map.xy_idx(x, y + 1 as i32);
+                    let mut idx = map.xy_idx(x, y + 1);
 
                     while idx < MAP_COUNT {
                         if map.occupied[idx] {
@@ -414,7 +1183,7 @@ pub fn draw_construction_selecting(ecs: &mut World, ctx: &mut Rltk) -> Construct
 
                     return ConstructionSelectingResult::NoSelection { x, y };
                 }
-                VirtualKeyCode::H => {
+                Action::CursorLeft => {
                     let map = ecs.fetch::<Map>();
                     let mut idx = map.xy_idx(x, y);
                     if idx == 0 {
@@ -437,7 +1206,7 @@ pub fn draw_construction_selecting(ecs: &mut World, ctx: &mut Rltk) -> Construct
 
                     return ConstructionSelectingResult::NoSelection { x, y };
                 }
-                VirtualKeyCode::L => {
+                Action::CursorRight => {
                     let map = ecs.fetch::<Map>();
                     let mut idx = map.xy_idx(x, y) + 1;
                     while idx < MAP_COUNT {
@@ -458,34 +1227,1038 @@ pub fn draw_construction_selecting(ecs: &mut World, ctx: &mut Rltk) -> Construct
     ConstructionSelectingResult::Escape
 }
 
-fn draw_construction_info(
-    ctx: &mut Rltk,
-    player_stats: &mut PlayerStats,
-    building: &mut Building,
-    name: &Name,
-    generator: Option<&mut Generator>,
-    detail: &BuildingDetail,
-) {
-    let mut info_x = building.rect.x2;
-    if info_x + CONSTRUCTION_INFO_WIDTH as i32 >= (MAP_PADDING_LEFT + MAP_WIDTH) as i32 {
-        info_x = building.rect.x1 - 1 - CONSTRUCTION_INFO_WIDTH as i32;
-    }
-    let mut info_y = building.rect.y1;
-    if info_y + CONSTRUCTION_INFO_HEIGHT as i32 >= (WINDOW_HEIGHT - MAP_PADDING_BOTTOM) as i32 {
-        info_y = info_y
-            - (info_y + CONSTRUCTION_INFO_HEIGHT as i32 - WINDOW_HEIGHT as i32
-                + MAP_PADDING_BOTTOM as i32);
-    }
-    ctx.draw_box(
-        info_x,
-        info_y,
-        CONSTRUCTION_INFO_WIDTH,
-        CONSTRUCTION_INFO_HEIGHT,
-        RGB::named(rltk::WHITE),
-        RGB::named(rltk::BLACK),
-    );
+pub const TEAR_DOWN_CONFIRM_WIDTH: usize = 40;
+pub const TEAR_DOWN_CONFIRM_HEIGHT: usize = 9;
+pub const TEAR_DOWN_CONFIRM_X: usize = (WINDOW_WIDTH - TEAR_DOWN_CONFIRM_WIDTH) / 2;
+pub const TEAR_DOWN_CONFIRM_Y: usize = (WINDOW_HEIGHT - TEAR_DOWN_CONFIRM_HEIGHT) / 2;
+#[derive(PartialEq, Copy, Clone)]
+pub enum TearDownConfirmResult {
+    NoResponse,
+    Cancel,
+    Confirm { entity: Entity },
+}
 
-    // construction name
+pub fn draw_tear_down_confirm(ecs: &mut World, ctx: &mut Rltk) -> TearDownConfirmResult {
+    let runstate = *ecs.fetch::<RunState>();
+
+    if let RunState::ConfirmTearDown { x, y } = runstate {
+        let entity = match utils::building_at(ecs, x, y) {
+            Some(entity) => entity,
+            None => return TearDownConfirmResult::Cancel,
+        };
+
+        let building_storage = ecs.read_storage::<Building>();
+        let name_storage = ecs.read_storage::<Name>();
+        let building = building_storage.get(entity).unwrap();
+        let name = name_storage.get(entity).unwrap();
+        let manifest = ecs.fetch::<ConstructionManifest>();
+
+        let camera = ecs.fetch::<Camera>();
+        for i in building.rect.y1..building.rect.y2 {
+            for j in building.rect.x1..building.rect.x2 {
+                if let Some((screen_x, screen_y)) = camera.to_screen(j, i) {
+                    ctx.set_bg(screen_x, screen_y, RGB::named(rltk::RED));
+                }
+            }
+        }
+
+        ctx.draw_box(
+            TEAR_DOWN_CONFIRM_X,
+            TEAR_DOWN_CONFIRM_Y,
+            TEAR_DOWN_CONFIRM_WIDTH,
+            TEAR_DOWN_CONFIRM_HEIGHT,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+        );
+        ctx.print_color(
+            TEAR_DOWN_CONFIRM_X + 1,
+            TEAR_DOWN_CONFIRM_Y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            "Tear down",
+        );
+        ctx.print_color(
+            TEAR_DOWN_CONFIRM_X + 2,
+            TEAR_DOWN_CONFIRM_Y + 2,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("Tear down {} (Level {})?", name.name, building.level),
+        );
+
+        if let Some(detail) = manifest.buildings.iter().find(|b| b.name == name.name) {
+            let refund = utils::tear_down_total_refund(
+                ecs,
+                entity,
+                detail,
+                building,
+                manifest.tear_down_refund,
+            );
+            let refund_info: Vec<String> = manifest
+                .resources
+                .iter()
+                .filter_map(|resource| {
+                    refund
+                        .get(&resource.id)
+                        .map(|amount| format!("{}: {}", resource.name, amount))
+                })
+                .collect();
+            ctx.print_color(
+                TEAR_DOWN_CONFIRM_X + 2,
+                TEAR_DOWN_CONFIRM_Y + 4,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::BLACK),
+                format!("Refund ({}%):", manifest.tear_down_refund),
+            );
+            ctx.print_color(
+                TEAR_DOWN_CONFIRM_X + 2,
+                TEAR_DOWN_CONFIRM_Y + 5,
+                RGB::named(rltk::YELLOW),
+                RGB::named(rltk::BLACK),
+                refund_info.join("  "),
+            );
+        }
+
+        let bindings = ecs.fetch::<KeyBindings>();
+        let confirm = format!("[{}] Confirm", bindings.label(Action::Yes));
+        let cancel = format!("[{}] Cancel", bindings.label(Action::No));
+        let answer_y = (TEAR_DOWN_CONFIRM_Y + TEAR_DOWN_CONFIRM_HEIGHT) as i32;
+        let cancel_x = TEAR_DOWN_CONFIRM_X as i32 + 3 + confirm.len() as i32;
+        ctx.print_color(
+            TEAR_DOWN_CONFIRM_X as i32 + 1,
+            answer_y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            &confirm,
+        );
+        ctx.print_color(
+            cancel_x,
+            answer_y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            &cancel,
+        );
+
+        // mouse
+        if ctx.left_click {
+            if mouse_over(
+                ctx,
+                TEAR_DOWN_CONFIRM_X as i32 + 1,
+                answer_y,
+                confirm.len() as i32,
+            ) {
+                return TearDownConfirmResult::Confirm { entity };
+            }
+            if mouse_over(ctx, cancel_x, answer_y, cancel.len() as i32) {
+                return TearDownConfirmResult::Cancel;
+            }
+        }
+
+        // control
+        let action = ecs
+            .fetch::<KeyBindings>()
+            .action(ctx.key, &[Action::Yes, Action::No, Action::Cancel]);
+        return match action {
+            Some(Action::Yes) => TearDownConfirmResult::Confirm { entity },
+            Some(_) => TearDownConfirmResult::Cancel,
+            None => TearDownConfirmResult::NoResponse,
+        };
+    }
+
+    TearDownConfirmResult::Cancel
+}
+
+pub const BUILD_QUEUE_WIDTH: usize = 56;
+pub const BUILD_QUEUE_HEIGHT: usize = 24;
+pub const BUILD_QUEUE_X: usize = MAP_PADDING_LEFT + 2;
+pub const BUILD_QUEUE_Y: usize = MAP_PADDING_UP + 2;
+/// Queue rows that fit between the header and the footer.
+const BUILD_QUEUE_ROWS: usize = BUILD_QUEUE_HEIGHT - 6;
+#[derive(PartialEq, Copy, Clone)]
+pub enum BuildQueueResult {
+    Close,
+    NoSelection { selected_idx: usize },
+    Cancel { entity: Entity, selected_idx: usize },
+}
+
+pub fn draw_build_queue(ecs: &mut World, ctx: &mut Rltk) -> BuildQueueResult {
+    let runstate = *ecs.fetch::<RunState>();
+
+    if let RunState::BuildQueue { selected_idx } = runstate {
+        let queue = construction_system::build_queue(ecs);
+        let selected_idx = min(selected_idx, queue.len().saturating_sub(1));
+        let site_storage = ecs.read_storage::<ConstructionSite>();
+        let manifest = ecs.fetch::<ConstructionManifest>();
+        let bindings = ecs.fetch::<KeyBindings>();
+        let camera = ecs.fetch::<Camera>();
+        let current = ecs.fetch::<GameClock>().now();
+
+        ctx.draw_box(
+            BUILD_QUEUE_X,
+            BUILD_QUEUE_Y,
+            BUILD_QUEUE_WIDTH,
+            BUILD_QUEUE_HEIGHT,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+        );
+        ctx.print_color(
+            BUILD_QUEUE_X + 1,
+            BUILD_QUEUE_Y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            "Build queue",
+        );
+        let busy = queue
+            .iter()
+            .filter(|entity| site_storage.get(**entity).unwrap().finish_at.is_some())
+            .count();
+        ctx.print_color(
+            BUILD_QUEUE_X + 2,
+            BUILD_QUEUE_Y + 1,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("Builders: {} / {}", busy, manifest.builder_slots),
+        );
+
+        if queue.is_empty() {
+            ctx.print_color(
+                BUILD_QUEUE_X + 2,
+                BUILD_QUEUE_Y + 3,
+                RGB::named(rltk::GRAY60),
+                RGB::named(rltk::BLACK),
+                "Nothing to build.",
+            );
+        }
+
+        // keep the selected row in view
+        let first = (selected_idx + 1).saturating_sub(BUILD_QUEUE_ROWS);
+        for (row, (idx, entity)) in queue
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(BUILD_QUEUE_ROWS)
+            .enumerate()
+        {
+            let site = site_storage.get(*entity).unwrap();
+            let status = match site.finish_at {
+                Some(finish_at) => format!(
+                    "{}% ({}s left)",
+                    construction_system::progress_percent(site, current),
+                    max(finish_at - current, 0)
+                ),
+                None => "waiting for a builder".to_string(),
+            };
+            let color = if idx == selected_idx {
+                RGB::named(rltk::MAGENTA)
+            } else {
+                RGB::named(rltk::WHITE)
+            };
+            ctx.print_color(
+                BUILD_QUEUE_X + 2,
+                BUILD_QUEUE_Y + 3 + row,
+                color,
+                RGB::named(rltk::BLACK),
+                format!("{:<20} {}", site.name, status),
+            );
+        }
+
+        // highlight the selected site and show what cancelling it returns
+        if let Some(site) = queue.get(selected_idx).and_then(|e| site_storage.get(*e)) {
+            for y in site.rect.y1..site.rect.y2 {
+                for x in site.rect.x1..site.rect.x2 {
+                    if let Some((screen_x, screen_y)) = camera.to_screen(x, y) {
+                        ctx.set_bg(screen_x, screen_y, RGB::named(rltk::MAGENTA));
+                    }
+                }
+            }
+            ctx.print_color(
+                BUILD_QUEUE_X + 2,
+                BUILD_QUEUE_Y + BUILD_QUEUE_HEIGHT - 2,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::BLACK),
+                format!("Refund: {}", rates_info(&manifest, &site.cost)),
+            );
+        }
+
+        let cancel = format!("[{}] Cancel build", bindings.label(Action::CancelBuild));
+        let close = format!("[{}] Close", bindings.label(Action::Cancel));
+        let footer_y = (BUILD_QUEUE_Y + BUILD_QUEUE_HEIGHT) as i32;
+        ctx.print_color(
+            BUILD_QUEUE_X as i32 + 1,
+            footer_y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            &cancel,
+        );
+        ctx.print_color(
+            BUILD_QUEUE_X as i32 + 3 + cancel.len() as i32,
+            footer_y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            &close,
+        );
+
+        // mouse
+        let hovered = (first..min(queue.len(), first + BUILD_QUEUE_ROWS)).find(|idx| {
+            mouse_over(
+                ctx,
+                BUILD_QUEUE_X as i32 + 2,
+                (BUILD_QUEUE_Y + 3 + idx - first) as i32,
+                BUILD_QUEUE_WIDTH as i32 - 3,
+            )
+        });
+        if let Some(idx) = hovered {
+            if ctx.left_click || ecs.fetch::<MouseCursor>().moved {
+                return BuildQueueResult::NoSelection { selected_idx: idx };
+            }
+        } else if ctx.left_click {
+            let (mouse_x, mouse_y) = ctx.mouse_pos();
+            let panel = Rect::new(
+                BUILD_QUEUE_X as i32,
+                BUILD_QUEUE_Y as i32,
+                BUILD_QUEUE_WIDTH as i32 + 1,
+                BUILD_QUEUE_HEIGHT as i32 + 1,
+            );
+            if mouse_over(ctx, BUILD_QUEUE_X as i32 + 1, footer_y, cancel.len() as i32) {
+                if let Some(entity) = queue.get(selected_idx) {
+                    return BuildQueueResult::Cancel {
+                        entity: *entity,
+                        selected_idx,
+                    };
+                }
+            } else if !panel.contains(mouse_x, mouse_y) {
+                return BuildQueueResult::Close;
+            }
+        }
+
+        // control
+        let action = bindings.action(
+            ctx.key,
+            &[
+                Action::Cancel,
+                Action::ShowBuildQueue,
+                Action::CancelBuild,
+                Action::CursorUp,
+                Action::CursorDown,
+            ],
+        );
+        return match action {
+            Some(Action::Cancel) | Some(Action::ShowBuildQueue) => BuildQueueResult::Close,
+            Some(Action::CancelBuild) => match queue.get(selected_idx) {
+                Some(entity) => BuildQueueResult::Cancel {
+                    entity: *entity,
+                    selected_idx,
+                },
+                None => BuildQueueResult::NoSelection { selected_idx },
+            },
+            Some(Action::CursorUp) => BuildQueueResult::NoSelection {
+                selected_idx: selected_idx.saturating_sub(1),
+            },
+            Some(Action::CursorDown) => BuildQueueResult::NoSelection {
+                selected_idx: min(selected_idx + 1, queue.len().saturating_sub(1)),
+            },
+            _ => BuildQueueResult::NoSelection { selected_idx },
+        };
+    }
+
+    BuildQueueResult::Close
+}
+
+pub const KEY_BINDINGS_WIDTH: usize = 70;
+pub const KEY_BINDINGS_HEIGHT: usize = Action::ALL.len() + 5;
+pub const KEY_BINDINGS_X: usize = (WINDOW_WIDTH - KEY_BINDINGS_WIDTH) / 2;
+pub const KEY_BINDINGS_Y: usize = (WINDOW_HEIGHT - KEY_BINDINGS_HEIGHT) / 2;
+#[derive(PartialEq, Copy, Clone)]
+pub enum KeyBindingsResult {
+    NoResponse,
+    Close,
+}
+
+pub fn draw_key_bindings(ecs: &mut World, ctx: &mut Rltk) -> KeyBindingsResult {
+    let bindings = ecs.fetch::<KeyBindings>();
+
+    ctx.draw_box(
+        KEY_BINDINGS_X,
+        KEY_BINDINGS_Y,
+        KEY_BINDINGS_WIDTH,
+        KEY_BINDINGS_HEIGHT,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        KEY_BINDINGS_X + 1,
+        KEY_BINDINGS_Y,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Key bindings",
+    );
+
+    for (offset, action) in Action::ALL.iter().enumerate() {
+        ctx.print_color(
+            KEY_BINDINGS_X + 2,
+            KEY_BINDINGS_Y + 2 + offset,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            action.description(),
+        );
+        let keys: Vec<String> = bindings
+            .keys(*action)
+            .iter()
+            .map(|key| key_name(*key))
+            .collect();
+        ctx.print_color(
+            KEY_BINDINGS_X + 45,
+            KEY_BINDINGS_Y + 2 + offset,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            keys.join(", "),
+        );
+    }
+
+    ctx.print_color(
+        KEY_BINDINGS_X + 2,
+        KEY_BINDINGS_Y + KEY_BINDINGS_HEIGHT - 2,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        format!("Edit {} to change them", KEY_BINDINGS_PATH),
+    );
+    ctx.print_color(
+        KEY_BINDINGS_X + 1,
+        KEY_BINDINGS_Y + KEY_BINDINGS_HEIGHT,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        format!("[{}] to close", bindings.label(Action::Cancel)),
+    );
+
+    // control
+    if ctx.left_click
+        || bindings
+            .action(ctx.key, &[Action::Cancel, Action::ShowBindings])
+            .is_some()
+    {
+        return KeyBindingsResult::Close;
+    }
+
+    KeyBindingsResult::NoResponse
+}
+
+pub const MESSAGE_LOG_WIDTH: usize = 110;
+pub const MESSAGE_LOG_HEIGHT: usize = 50;
+pub const MESSAGE_LOG_X: usize = (WINDOW_WIDTH - MESSAGE_LOG_WIDTH) / 2;
+pub const MESSAGE_LOG_Y: usize = (WINDOW_HEIGHT - MESSAGE_LOG_HEIGHT) / 2;
+/// Entries visible at once.
+const MESSAGE_LOG_ROWS: usize = MESSAGE_LOG_HEIGHT - 4;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MessageLogResult {
+    Close,
+    NoSelection { scroll: usize },
+}
+
+/// The whole message log, newest at the bottom. `scroll` counts the entries
+/// hidden below the window.
+pub fn draw_message_log(ecs: &mut World, ctx: &mut Rltk) -> MessageLogResult {
+    let runstate = *ecs.fetch::<RunState>();
+    let log = ecs.fetch::<GameLog>();
+    let bindings = ecs.fetch::<KeyBindings>();
+
+    let max_scroll = log.entries.len().saturating_sub(MESSAGE_LOG_ROWS);
+    let scroll = match runstate {
+        RunState::MessageLog { scroll } => min(scroll, max_scroll),
+        _ => 0,
+    };
+
+    ctx.draw_box(
+        MESSAGE_LOG_X,
+        MESSAGE_LOG_Y,
+        MESSAGE_LOG_WIDTH,
+        MESSAGE_LOG_HEIGHT,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        MESSAGE_LOG_X + 1,
+        MESSAGE_LOG_Y,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Message log",
+    );
+
+    let last = log.entries.len() - scroll;
+    let first = last.saturating_sub(MESSAGE_LOG_ROWS);
+    let founded_at = gamelog::founded_at(ecs);
+    for (offset, entry) in log.entries[first..last].iter().enumerate() {
+        let line = format!(
+            "{} {:<8} {}",
+            entry.time_label(founded_at),
+            entry.category.label(),
+            entry.text
+        );
+        let line: String = line.chars().take(MESSAGE_LOG_WIDTH - 3).collect();
+        ctx.print_color(
+            MESSAGE_LOG_X + 2,
+            MESSAGE_LOG_Y + 2 + offset,
+            entry.category.color(),
+            RGB::named(rltk::BLACK),
+            line,
+        );
+    }
+
+    ctx.print_color(
+        MESSAGE_LOG_X + 2,
+        MESSAGE_LOG_Y + MESSAGE_LOG_HEIGHT - 1,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        format!(
+            "Entries {}-{} of {}",
+            min(first + 1, last),
+            last,
+            log.entries.len()
+        ),
+    );
+    ctx.print_color(
+        MESSAGE_LOG_X + 1,
+        MESSAGE_LOG_Y + MESSAGE_LOG_HEIGHT,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        format!(
+            "[{}/{}] Scroll  [{}] Close",
+            bindings.label(Action::CursorUp),
+            bindings.label(Action::CursorDown),
+            bindings.label(Action::Cancel)
+        ),
+    );
+
+    // control
+    if ctx.left_click {
+        return MessageLogResult::Close;
+    }
+    let action = bindings.action(
+        ctx.key,
+        &[
+            Action::Cancel,
+            Action::ShowLog,
+            Action::CursorUp,
+            Action::ScrollUp,
+            Action::CursorDown,
+            Action::ScrollDown,
+        ],
+    );
+    match action {
+        Some(Action::Cancel) | Some(Action::ShowLog) => MessageLogResult::Close,
+        Some(Action::CursorUp) | Some(Action::ScrollUp) => MessageLogResult::NoSelection {
+            scroll: min(scroll + 1, max_scroll),
+        },
+        Some(Action::CursorDown) | Some(Action::ScrollDown) => MessageLogResult::NoSelection {
+            scroll: scroll.saturating_sub(1),
+        },
+        _ => MessageLogResult::NoSelection { scroll },
+    }
+}
+
+pub const STATISTICS_WIDTH: usize = 136;
+pub const STATISTICS_HEIGHT: usize = 62;
+pub const STATISTICS_X: usize = (WINDOW_WIDTH - STATISTICS_WIDTH) / 2;
+pub const STATISTICS_Y: usize = (WINDOW_HEIGHT - STATISTICS_HEIGHT) / 2;
+const AMOUNT_GRAPH_HEIGHT: usize = 16;
+/// Odd, so the zero line sits in the middle.
+const RATE_GRAPH_HEIGHT: usize = 11;
+/// Column the graphs start at, after the value labels.
+const GRAPH_X: usize = STATISTICS_X + 12;
+/// Rows of the per-building breakdown.
+const BREAKDOWN_ROWS: usize = 14;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum StatisticsResult {
+    Close,
+    NoSelection {
+        resource_idx: usize,
+        long_term: bool,
+    },
+}
+
+/// Graphs of one resource's amount and net rate over the recent or the
+/// long-term history, what each type of building produces, and what was
+/// lost to the storage caps.
+pub fn draw_statistics(ecs: &mut World, ctx: &mut Rltk) -> StatisticsResult {
+    let (resource_idx, long_term) = match *ecs.fetch::<RunState>() {
+        RunState::Statistics {
+            resource_idx,
+            long_term,
+        } => (resource_idx, long_term),
+        _ => (0, false),
+    };
+    let manifest = ecs.fetch::<ConstructionManifest>();
+    // nothing to graph; the manifest check should have caught this
+    if manifest.resources.is_empty() {
+        return StatisticsResult::Close;
+    }
+    let bindings = ecs.fetch::<KeyBindings>();
+    let resource_idx = min(resource_idx, manifest.resources.len() - 1);
+    let resource = &manifest.resources[resource_idx];
+    let color = utils::parse_color(&resource.fg).unwrap_or_else(|| RGB::named(rltk::YELLOW));
+
+    ctx.draw_box(
+        STATISTICS_X,
+        STATISTICS_Y,
+        STATISTICS_WIDTH,
+        STATISTICS_HEIGHT,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        STATISTICS_X + 1,
+        STATISTICS_Y,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Statistics",
+    );
+
+    // resource tabs
+    let mut tabs = Vec::new();
+    let mut tab_x = STATISTICS_X as i32 + 2;
+    for (idx, tab) in manifest.resources.iter().enumerate() {
+        let label = format!(" {} ", tab.name);
+        let bg = if idx == resource_idx {
+            RGB::named(rltk::MAGENTA)
+        } else {
+            RGB::named(rltk::BLACK)
+        };
+        ctx.print_color(
+            tab_x,
+            STATISTICS_Y as i32 + 2,
+            RGB::named(rltk::WHITE),
+            bg,
+            &label,
+        );
+        tabs.push((tab_x, label.len() as i32));
+        tab_x += label.len() as i32 + 1;
+    }
+
+    let player = *ecs.fetch::<Entity>();
+    let history_storage = ecs.read_storage::<ResourceHistory>();
+    let history = history_storage.get(player);
+    let (samples, interval) = match history {
+        Some(history) if long_term => (
+            history.long_term.samples.iter().collect(),
+            history.long_term.interval,
+        ),
+        Some(history) => (
+            history.recent.samples.iter().collect(),
+            history.recent.interval,
+        ),
+        None => (Vec::new(), RECENT_SAMPLE_SECONDS),
+    };
+    let samples: Vec<ResourceSample> = samples
+        .iter()
+        .map(|sample| sample.get(&resource.id).copied().unwrap_or_default())
+        .collect();
+    let span = span_label(interval * HISTORY_SAMPLES as i64);
+    let range_label = format!("Range: last {}", span);
+    ctx.print_color(
+        STATISTICS_X + 2,
+        STATISTICS_Y + 3,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        &range_label,
+    );
+
+    // amount, with the cap as a dim line
+    let amount_y = STATISTICS_Y + 5;
+    ctx.print_color(
+        STATISTICS_X + 2,
+        amount_y,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        format!("{} stored", resource.name),
+    );
+    let scale = samples
+        .iter()
+        .map(|sample| max(sample.cap, sample.amount))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    draw_line_graph(
+        ctx,
+        amount_y + 1,
+        scale,
+        &samples
+            .iter()
+            .map(|sample| (sample.amount, sample.cap))
+            .collect::<Vec<(i32, i32)>>(),
+        color,
+    );
+    draw_time_axis(ctx, amount_y + 1 + AMOUNT_GRAPH_HEIGHT, &span);
+
+    // net rate, above and below a zero line
+    let rate_y = amount_y + AMOUNT_GRAPH_HEIGHT + 3;
+    ctx.print_color(
+        STATISTICS_X + 2,
+        rate_y,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        format!("{} per second", resource.name),
+    );
+    let rates: Vec<i32> = samples.iter().map(|sample| sample.rate).collect();
+    draw_bar_graph(ctx, rate_y + 1, &rates);
+    draw_time_axis(ctx, rate_y + 1 + RATE_GRAPH_HEIGHT, &span);
+
+    // production by building type
+    let breakdown_y = rate_y + RATE_GRAPH_HEIGHT + 3;
+    ctx.print_color(
+        STATISTICS_X + 2,
+        breakdown_y,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        "Production by building type (per second)",
+    );
+    let breakdown = resource_system::production_by_building(ecs);
+    for (offset, (name, count, rates)) in breakdown.iter().take(BREAKDOWN_ROWS).enumerate() {
+        let rates_info: Vec<String> = manifest
+            .resources
+            .iter()
+            .filter_map(|resource| {
+                rates
+                    .get(&resource.id)
+                    .filter(|rate| **rate != 0)
+                    .map(|rate| format!("{:+} {}", rate, resource.name))
+            })
+            .collect();
+        ctx.print_color(
+            STATISTICS_X + 2,
+            breakdown_y + 1 + offset,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("{:<16} x{:<3} {}", name, count, rates_info.join(", ")),
+        );
+    }
+
+    // lost to the caps
+    let lost_x = STATISTICS_X + 96;
+    ctx.print_color(
+        lost_x,
+        breakdown_y,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        "Lost to the storage cap",
+    );
+    for (offset, resource) in manifest.resources.iter().enumerate() {
+        let lost = history
+            .and_then(|history| history.lost_to_cap.get(&resource.id))
+            .copied()
+            .unwrap_or(0);
+        let fg = if lost > 0 {
+            *utils::MORANDI_RED
+        } else {
+            RGB::named(rltk::GRAY60)
+        };
+        ctx.print_color(
+            lost_x,
+            breakdown_y + 1 + offset,
+            fg,
+            RGB::named(rltk::BLACK),
+            format!("{:<10} {}", resource.name, lost),
+        );
+    }
+
+    ctx.print_color(
+        STATISTICS_X + 1,
+        STATISTICS_Y + STATISTICS_HEIGHT,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        format!(
+            "[{}/{}] Resource  [{}/{}] Range  [{}] Close",
+            bindings.label(Action::CursorLeft),
+            bindings.label(Action::CursorRight),
+            bindings.label(Action::CursorUp),
+            bindings.label(Action::CursorDown),
+            bindings.label(Action::Cancel)
+        ),
+    );
+
+    // mouse: tabs pick the resource, the range line toggles, outside closes
+    if ctx.left_click {
+        if let Some(idx) = tabs
+            .iter()
+            .position(|(x, width)| mouse_over(ctx, *x, STATISTICS_Y as i32 + 2, *width))
+        {
+            return StatisticsResult::NoSelection {
+                resource_idx: idx,
+                long_term,
+            };
+        }
+        if mouse_over(
+            ctx,
+            STATISTICS_X as i32 + 2,
+            STATISTICS_Y as i32 + 3,
+            range_label.len() as i32,
+        ) {
+            return StatisticsResult::NoSelection {
+                resource_idx,
+                long_term: !long_term,
+            };
+        }
+        let (mouse_x, mouse_y) = ctx.mouse_pos();
+        let screen = Rect::new(
+            STATISTICS_X as i32,
+            STATISTICS_Y as i32,
+            STATISTICS_WIDTH as i32 + 1,
+            STATISTICS_HEIGHT as i32 + 1,
+        );
+        if !screen.contains(mouse_x, mouse_y) {
+            return StatisticsResult::Close;
+        }
+    }
+
+    // control
+    let count = manifest.resources.len();
+    let action = bindings.action(
+        ctx.key,
+        &[
+            Action::Cancel,
+            Action::ShowStatistics,
+            Action::CursorLeft,
+            Action::ScrollLeft,
+            Action::CursorRight,
+            Action::ScrollRight,
+            Action::CursorUp,
+            Action::ScrollUp,
+            Action::CursorDown,
+            Action::ScrollDown,
+        ],
+    );
+    match action {
+        Some(Action::Cancel) | Some(Action::ShowStatistics) => StatisticsResult::Close,
+        Some(Action::CursorLeft) | Some(Action::ScrollLeft) => StatisticsResult::NoSelection {
+            resource_idx: (resource_idx + count - 1) % count,
+            long_term,
+        },
+        Some(Action::CursorRight) | Some(Action::ScrollRight) => StatisticsResult::NoSelection {
+            resource_idx: (resource_idx + 1) % count,
+            long_term,
+        },
+        Some(Action::CursorUp)
+        | Some(Action::ScrollUp)
+        | Some(Action::CursorDown)
+        | Some(Action::ScrollDown) => StatisticsResult::NoSelection {
+            resource_idx,
+            long_term: !long_term,
+        },
+        _ => StatisticsResult::NoSelection {
+            resource_idx,
+            long_term,
+        },
+    }
+}
+
+/// "10 minutes", "6 hours".
+fn span_label(seconds: i64) -> String {
+    if seconds >= 3600 {
+        format!("{} hours", seconds / 3600)
+    } else {
+        format!("{} minutes", seconds / 60)
+    }
+}
+
+/// Plots `(value, cap)` pairs from 0 at the bottom to `scale` at the top,
+/// newest on the right. Neighbouring points are joined by vertical strokes
+/// and the cap is drawn as a dim line behind them.
+fn draw_line_graph(ctx: &mut Rltk, y: usize, scale: i32, points: &[(i32, i32)], color: RGB) {
+    let bottom = (AMOUNT_GRAPH_HEIGHT - 1) as i64;
+    let row_of = |value: i32| -> i32 {
+        let value = value.clamp(0, scale) as i64;
+        y as i32 + (bottom - value * bottom / scale as i64) as i32
+    };
+
+    ctx.print_color(
+        STATISTICS_X + 2,
+        y,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        format!("{:>8}", scale),
+    );
+    ctx.print_color(
+        STATISTICS_X + 2,
+        y + AMOUNT_GRAPH_HEIGHT - 1,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        format!("{:>8}", 0),
+    );
+    for row in 0..AMOUNT_GRAPH_HEIGHT {
+        ctx.set(
+            GRAPH_X - 1,
+            y + row,
+            RGB::named(rltk::GRAY60),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('│'),
+        );
+    }
+
+    let first_x = GRAPH_X + HISTORY_SAMPLES - points.len();
+    let mut previous: Option<i32> = None;
+    for (offset, (value, cap)) in points.iter().enumerate() {
+        let x = (first_x + offset) as i32;
+        ctx.set(
+            x,
+            row_of(*cap),
+            RGB::named(rltk::GRAY30),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('─'),
+        );
+
+        let row = row_of(*value);
+        if let Some(previous) = previous {
+            for stroke in min(previous, row) + 1..max(previous, row) {
+                ctx.set(
+                    x,
+                    stroke,
+                    color,
+                    RGB::named(rltk::BLACK),
+                    rltk::to_cp437('│'),
+                );
+            }
+        }
+        ctx.set(x, row, color, RGB::named(rltk::BLACK), rltk::to_cp437('•'));
+        previous = Some(row);
+    }
+}
+
+/// Bars up from the middle row for gains, down for losses, newest on the
+/// right, scaled to the largest rate shown.
+fn draw_bar_graph(ctx: &mut Rltk, y: usize, rates: &[i32]) {
+    let half = (RATE_GRAPH_HEIGHT / 2) as i32;
+    let zero_y = y as i32 + half;
+    let scale = rates
+        .iter()
+        .map(|rate| rate.abs())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    ctx.print_color(
+        STATISTICS_X + 2,
+        y,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        format!("{:>+8}", scale),
+    );
+    ctx.print_color(
+        STATISTICS_X + 2,
+        zero_y,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        format!("{:>8}", 0),
+    );
+    ctx.print_color(
+        STATISTICS_X + 2,
+        y + RATE_GRAPH_HEIGHT - 1,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        format!("{:>8}", -scale),
+    );
+    for row in 0..RATE_GRAPH_HEIGHT {
+        ctx.set(
+            GRAPH_X - 1,
+            y + row,
+            RGB::named(rltk::GRAY60),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('│'),
+        );
+    }
+    for x in GRAPH_X..GRAPH_X + HISTORY_SAMPLES {
+        ctx.set(
+            x,
+            zero_y,
+            RGB::named(rltk::GRAY30),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('─'),
+        );
+    }
+
+    let first_x = GRAPH_X + HISTORY_SAMPLES - rates.len();
+    for (offset, rate) in rates.iter().enumerate() {
+        // a rate that isn't zero always gets at least one cell
+        let mut height = (rate.abs() as i64 * half as i64 / scale as i64) as i32;
+        if *rate != 0 {
+            height = max(height, 1);
+        }
+        let (direction, fg) = if *rate > 0 {
+            (-1, RGB::named(rltk::GREEN))
+        } else {
+            (1, *utils::MORANDI_RED)
+        };
+        for step in 1..=height {
+            ctx.set(
+                (first_x + offset) as i32,
+                zero_y + direction * step,
+                fg,
+                RGB::named(rltk::BLACK),
+                rltk::to_cp437('█'),
+            );
+        }
+    }
+}
+
+/// "-10 minutes" under the left end of a graph, "now" under the right.
+fn draw_time_axis(ctx: &mut Rltk, y: usize, span: &str) {
+    ctx.print_color(
+        GRAPH_X,
+        y,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        format!("-{}", span),
+    );
+    ctx.print_color(
+        GRAPH_X + HISTORY_SAMPLES - 3,
+        y,
+        RGB::named(rltk::GRAY60),
+        RGB::named(rltk::BLACK),
+        "now",
+    );
+}
+
+fn draw_construction_info(
+    ctx: &mut Rltk,
+    ecs: &World,
+    entity: Entity,
+    player_stats: &PlayerStats,
+    building: &Building,
+    name: &Name,
+    manifest: &ConstructionManifest,
+) -> Option<ConstructionSelectingResult> {
+    let generator_storage = ecs.read_storage::<Generator>();
+    let capacity_storage = ecs.read_storage::<Capacity>();
+    let converter_storage = ecs.read_storage::<Converter>();
+    let housing_storage = ecs.read_storage::<Housing>();
+    let workforce_storage = ecs.read_storage::<Workforce>();
+    let upkeep_storage = ecs.read_storage::<Upkeep>();
+    let garrison_storage = ecs.read_storage::<Garrison>();
+    let detail = manifest
+        .building(&name.name)
+        .expect("Building must have detail");
+    let (screen_x1, screen_y1) = ecs
+        .fetch::<Camera>()
+        .screen_pos(building.rect.x1, building.rect.y1);
+    let screen_x2 = screen_x1 + building.rect.x2 - building.rect.x1;
+    let mut info_x = screen_x2;
+    if info_x + CONSTRUCTION_INFO_WIDTH as i32 >= (MAP_PADDING_LEFT + VIEW_WIDTH) as i32 {
+        info_x = screen_x1 - 1 - CONSTRUCTION_INFO_WIDTH as i32;
+    }
+    let mut info_y = max(screen_y1, MAP_PADDING_UP as i32);
+    if info_y + CONSTRUCTION_INFO_HEIGHT as i32 >= (WINDOW_HEIGHT - MAP_PADDING_BOTTOM) as i32 {
+        info_y = info_y
+            - (info_y + CONSTRUCTION_INFO_HEIGHT as i32 - WINDOW_HEIGHT as i32
+                + MAP_PADDING_BOTTOM as i32);
+    }
+    ctx.draw_box(
+        info_x,
+        info_y,
+        CONSTRUCTION_INFO_WIDTH,
+        CONSTRUCTION_INFO_HEIGHT,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+
+    // construction name
     ctx.print_color(
         info_x + 1,
         info_y + 1,
@@ -512,18 +2285,17 @@ fn draw_construction_info(
     );
 
     // rate
-    if let Some(gen) = generator.as_ref() {
-        let rate_info;
-        match gen.resource_type {
-            ResourceType::Food => {
-                rate_info = format!("Food: +{}/sec", gen.rate);
-            }
-            ResourceType::Wood => {
-                rate_info = format!("Wood: +{}/sec", gen.rate);
-            }
-            ResourceType::Stone => {
-                rate_info = format!("Stone: +{}/sec", gen.rate);
-            }
+    let workforce = workforce_storage.get(entity);
+    if let Some(gen) = generator_storage.get(entity) {
+        let rate = resource_system::effective_rate(gen, workforce);
+        let mut rate_info = format!(
+            "{}: +{}/sec",
+            manifest.resource_name(&gen.resource_type),
+            rate
+        );
+        let full_rate = resource_system::effective_rate(gen, None);
+        if rate < full_rate {
+            rate_info = format!("{} (of {})", rate_info, full_rate);
         }
 
         ctx.print_color(
@@ -533,11 +2305,189 @@ fn draw_construction_info(
             RGB::named(rltk::BLACK),
             rate_info,
         );
+
+        // deposits
+        if let Some(radius) = gen.harvest_radius {
+            let left = deposits::remaining(
+                &ecs.fetch::<Map>(),
+                manifest,
+                &gen.resource_type,
+                &building.rect,
+                radius,
+            );
+            let (deposit_info, color) = if gen.exhausted || left == 0 {
+                ("Deposits exhausted".to_string(), *utils::MORANDI_RED)
+            } else {
+                (format!("Deposits: {} left", left), RGB::named(rltk::WHITE))
+            };
+            ctx.print_color(
+                info_x + 1,
+                info_y + 6,
+                color,
+                RGB::named(rltk::BLACK),
+                deposit_info,
+            );
+        }
+    }
+
+    // workers
+    if let Some(workforce) = workforce {
+        ctx.print_color(
+            info_x + 1,
+            info_y + 5,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!(
+                "Workers: {} / {} ({} idle)",
+                workforce.assigned,
+                workforce.slots,
+                utils::idle_workers(player_stats, &workforce_storage)
+            ),
+        );
+    }
+
+    // housing
+    if let Some(housing) = housing_storage.get(entity) {
+        ctx.print_color(
+            info_x + 1,
+            info_y + 4,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("Houses: {} citizens", housing.capacity),
+        );
+    }
+
+    // upkeep
+    if let Some(upkeep) = upkeep_storage.get(entity) {
+        ctx.print_color(
+            info_x + 1,
+            info_y + 7,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("Upkeep: {}/sec", rates_info(manifest, &upkeep.costs)),
+        );
+    }
+
+    // garrison
+    if let Some(garrison) = garrison_storage.get(entity) {
+        ctx.print_color(
+            info_x + 1,
+            info_y + 4,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("Garrison: {} / {}", garrison.soldiers, garrison.capacity),
+        );
+        let training_info = if garrison.soldiers < garrison.capacity {
+            let remaining = garrison.next_soldier - ecs.fetch::<GameClock>().now();
+            format!("Next soldier in {}s", remaining.max(0))
+        } else {
+            "Garrison is full".to_string()
+        };
+        ctx.print_color(
+            info_x + 1,
+            info_y + 5,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            training_info,
+        );
+        ctx.print_color(
+            info_x + 1,
+            info_y + 6,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("Cost: {}", rates_info(manifest, &garrison.cost)),
+        );
+    }
+
+    // storage
+    if let Some(capacity) = capacity_storage.get(entity) {
+        ctx.print_color(
+            info_x + 1,
+            info_y + 4,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("Storage: {}", capacity_info(manifest, capacity)),
+        );
+    }
+
+    // conversion
+    if let Some(converter) = converter_storage.get(entity) {
+        ctx.print_color(
+            info_x + 1,
+            info_y + 4,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("In:  {}/sec", rates_info(manifest, &converter.inputs)),
+        );
+        ctx.print_color(
+            info_x + 1,
+            info_y + 5,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("Out: {}/sec", rates_info(manifest, &converter.outputs)),
+        );
+
+        let (status, color) = match converter.status {
+            ConverterStatus::Running => ("Running", RGB::named(rltk::GREEN)),
+            ConverterStatus::Starved => ("Starved", *utils::MORANDI_RED),
+            ConverterStatus::Blocked => ("Blocked", RGB::named(rltk::ORANGE)),
+            ConverterStatus::Renovating => ("Renovating", RGB::named(rltk::YELLOW)),
+        };
+        ctx.print_color(
+            info_x + 1,
+            info_y + 6,
+            color,
+            RGB::named(rltk::BLACK),
+            format!("Status: {}", status),
+        );
     }
 
     // actions
+    let bindings = ecs.fetch::<KeyBindings>();
+    let add_label = bindings.label(Action::AddWorker);
+    let remove_label = bindings.label(Action::RemoveWorker);
+    let (x, y) = (building.rect.x1, building.rect.y1);
     let next_level = building.level + 1;
-    let action_line_y = info_y + CONSTRUCTION_INFO_HEIGHT as i32 - 3;
+    let current = ecs.fetch::<GameClock>().now();
+    let renovation_storage = ecs.read_storage::<Renovation>();
+    let renovation = renovation_storage.get(entity);
+    let mut actions = Vec::new();
+    if workforce.is_some() {
+        actions.push((
+            format!("[{}/{}] Workers", add_label, remove_label),
+            ConstructionSelectingResult::AssignWorker { x, y, delta: 1 },
+        ));
+    }
+    match renovation {
+        Some(renovation) => {
+            if let Some((resource_type, cost)) =
+                utils::instant_finish_cost(manifest, renovation, current)
+            {
+                actions.push((
+                    format!(
+                        "[{}] Finish now ({} {})",
+                        bindings.label(Action::FinishNow),
+                        cost,
+                        manifest.resource_name(&resource_type)
+                    ),
+                    ConstructionSelectingResult::FinishNow { x, y },
+                ));
+            }
+        }
+        None if next_level < detail.levels.len() as i32 => {
+            actions.push((
+                format!("[{}] Upgrade", bindings.label(Action::Upgrade)),
+                ConstructionSelectingResult::Upgrade { x, y },
+            ));
+        }
+        None => {}
+    }
+    actions.push((
+        format!("[{}] Tear down", bindings.label(Action::TearDown)),
+        ConstructionSelectingResult::TearDown { x, y },
+    ));
+
+    let action_line_y = info_y + CONSTRUCTION_INFO_HEIGHT as i32 - 1 - actions.len() as i32;
     ctx.draw_hollow_box(
         info_x,
         action_line_y,
@@ -546,26 +2496,43 @@ fn draw_construction_info(
         RGB::named(rltk::WHITE),
         RGB::named(rltk::BLACK),
     );
-    if next_level < detail.levels.len() as i32 {
+    for (offset, (action, _)) in actions.iter().enumerate() {
         ctx.print_color(
             info_x + 1,
-            action_line_y + 1,
+            action_line_y + 1 + offset as i32,
             RGB::named(rltk::WHITE),
             RGB::named(rltk::BLACK),
-            format!("[u] Upgrade"),
+            action,
         );
     }
-    ctx.print_color(
-        info_x + 1,
-        action_line_y + 2,
-        RGB::named(rltk::WHITE),
-        RGB::named(rltk::BLACK),
-        format!("[t] Tear down"),
-    );
 
-    // upgrade requirements
-    if next_level < detail.levels.len() as i32 {
-        let requirement_line_y = action_line_y - 5;
+    // upgrade in progress, or what the next one needs
+    if let Some(renovation) = renovation {
+        let renovation_line_y = action_line_y - 3;
+        ctx.draw_hollow_box(
+            info_x,
+            renovation_line_y,
+            CONSTRUCTION_INFO_WIDTH,
+            0,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+        );
+        ctx.print_color(
+            info_x + 1,
+            renovation_line_y + 1,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            format!("Upgrading to level {}", next_level),
+        );
+        ctx.print_color(
+            info_x + 1,
+            renovation_line_y + 2,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("{}s left", max(renovation.finish_at - current, 0)),
+        );
+    } else if next_level < detail.levels.len() as i32 {
+        let requirement_line_y = action_line_y - requirement_line_count(detail, next_level);
         ctx.draw_hollow_box(
             info_x,
             requirement_line_y,
@@ -578,6 +2545,7 @@ fn draw_construction_info(
         print_building_requirements(
             ctx,
             player_stats,
+            manifest,
             detail,
             next_level,
             info_x + 1,
@@ -585,28 +2553,197 @@ fn draw_construction_info(
         );
     }
 
-    // player control
-    match ctx.key {
-        Some(key) => match key {
-            VirtualKeyCode::U => {
-                // upgrade
-                if utils::requirements_check(player_stats, Some(building), detail, next_level) {
-                    utils::consume_resource(player_stats, detail, next_level);
-                    utils::upgrade_building(detail, building, generator, next_level);
-                }
-            }
-            VirtualKeyCode::T => {
-                // tear down
-            }
-            _ => {}
+    // mouse: a click on an action line triggers it, anywhere else on the
+    // panel is swallowed so it doesn't reach the map underneath
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    let panel = Rect::new(
+        info_x,
+        info_y,
+        CONSTRUCTION_INFO_WIDTH as i32 + 1,
+        CONSTRUCTION_INFO_HEIGHT as i32 + 1,
+    );
+    if !ctx.left_click || !panel.contains(mouse_x, mouse_y) {
+        return None;
+    }
+    let clicked = actions
+        .iter()
+        .enumerate()
+        .find(|(offset, (action, _))| {
+            mouse_over(
+                ctx,
+                info_x + 1,
+                action_line_y + 1 + *offset as i32,
+                action.len() as i32,
+            )
+        })
+        .map(|(_, (_, result))| *result);
+
+    // "[+/-] Workers": clicking "-]" removes a worker, the rest of the line adds one
+    let remove_x = info_x + 3 + add_label.len() as i32;
+    Some(match clicked {
+        Some(ConstructionSelectingResult::AssignWorker { x, y, .. })
+            if (remove_x..=remove_x + remove_label.len() as i32).contains(&mouse_x) =>
+        {
+            ConstructionSelectingResult::AssignWorker { x, y, delta: -1 }
+        }
+        Some(result) => result,
+        None => ConstructionSelectingResult::NoSelection { x, y },
+    })
+}
+
+/// Name, level and output of the building under the mouse, or how far along
+/// a construction site is.
+pub fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    let (tile_x, tile_y) = match ecs.fetch::<Camera>().to_world(mouse_x, mouse_y) {
+        Some(tile) => tile,
+        None => return,
+    };
+
+    let lines = match utils::building_under(ecs, tile_x, tile_y) {
+        Some(entity) => building_tooltip(ecs, entity),
+        None => match utils::site_under(ecs, tile_x, tile_y) {
+            Some(entity) => site_tooltip(ecs, entity),
+            None => return,
         },
-        None => {}
+    };
+
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as i32 + 2;
+    let height = lines.len() as i32 + 1;
+    // keep the box inside the map window, on whichever side of the mouse has room
+    let x = if mouse_x + 2 + width < (MAP_PADDING_LEFT + VIEW_WIDTH) as i32 {
+        mouse_x + 2
+    } else {
+        mouse_x - 2 - width
+    };
+    let y = min(mouse_y, (MAP_PADDING_UP + VIEW_HEIGHT) as i32 - 1 - height);
+    ctx.draw_box(
+        x,
+        y,
+        width,
+        height,
+        RGB::named(rltk::GREY),
+        RGB::named(rltk::BLACK),
+    );
+    for (offset, line) in lines.iter().enumerate() {
+        ctx.print_color(
+            x + 1,
+            y + 1 + offset as i32,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            line,
+        );
+    }
+}
+
+fn building_tooltip(ecs: &World, entity: Entity) -> Vec<String> {
+    let manifest = ecs.fetch::<ConstructionManifest>();
+    let building = ecs.read_storage::<Building>();
+    let building = building.get(entity).unwrap();
+    let mut lines = vec![format!("Level {}", building.level)];
+    if let Some(name) = ecs.read_storage::<Name>().get(entity) {
+        lines.insert(0, name.name.clone());
+    }
+    if let Some(gen) = ecs.read_storage::<Generator>().get(entity) {
+        let workforce = ecs.read_storage::<Workforce>();
+        lines.push(format!(
+            "{}: +{}/sec",
+            manifest.resource_name(&gen.resource_type),
+            resource_system::effective_rate(gen, workforce.get(entity))
+        ));
+    }
+    if let Some(converter) = ecs.read_storage::<Converter>().get(entity) {
+        lines.push(format!(
+            "Out: {}/sec",
+            rates_info(&manifest, &converter.outputs)
+        ));
+    }
+    if let Some(renovation) = ecs.read_storage::<Renovation>().get(entity) {
+        let left = renovation.finish_at - ecs.fetch::<GameClock>().now();
+        lines.push(format!("Upgrading: {}s left", max(left, 0)));
+    }
+
+    lines
+}
+
+fn site_tooltip(ecs: &World, entity: Entity) -> Vec<String> {
+    let site = ecs.read_storage::<ConstructionSite>();
+    let site = site.get(entity).unwrap();
+    let status = match site.finish_at {
+        Some(_) => format!(
+            "Under construction: {}%",
+            construction_system::progress_percent(site, ecs.fetch::<GameClock>().now())
+        ),
+        None => "Waiting for a builder".to_string(),
+    };
+
+    vec![site.name.clone(), status]
+}
+
+fn capacity_info(manifest: &ConstructionManifest, capacity: &Capacity) -> String {
+    let amounts: Vec<i32> = manifest
+        .resources
+        .iter()
+        .map(|resource| capacity.capacity.get(&resource.id).copied().unwrap_or(0))
+        .collect();
+    if amounts.len() > 1 && amounts[0] > 0 && amounts.iter().all(|amount| *amount == amounts[0]) {
+        return format!("+{} all", amounts[0]);
+    }
+
+    manifest
+        .resources
+        .iter()
+        .zip(amounts)
+        .filter(|(_, amount)| *amount > 0)
+        .map(|(resource, amount)| format!("+{} {}", amount, resource.name))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// e.g. "4 Wood, 1 Stone", in the order the manifest declares the resources.
+fn rates_info(manifest: &ConstructionManifest, rates: &HashMap<ResourceType, i32>) -> String {
+    manifest
+        .resources
+        .iter()
+        .filter_map(|resource| {
+            rates
+                .get(&resource.id)
+                .map(|rate| format!("{} {}", rate, resource.name))
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Rows `print_building_requirements` takes up for an upgrade to `next_level`.
+fn requirement_line_count(detail: &BuildingDetail, next_level: i32) -> i32 {
+    let level = &detail.levels[&next_level];
+    let mut count = 0;
+    if level.rate.is_some() {
+        count += 1;
+    }
+    if level.storage.is_some() {
+        count += 1;
+    }
+    if level.outputs.is_some() {
+        count += 1;
+    }
+    if level.upkeep.is_some() {
+        count += 1;
     }
+    if level.training.is_some() {
+        count += 1;
+    }
+    if let Some(requirements) = &level.requirements {
+        count += 1 + requirements.resources.len() as i32;
+    }
+
+    count
 }
 
 fn print_building_requirements(
     ctx: &mut Rltk,
     player_stats: &PlayerStats,
+    manifest: &ConstructionManifest,
     detail: &BuildingDetail,
     next_level: i32,
     x: i32,
@@ -616,16 +2753,56 @@ fn print_building_requirements(
 
     // next level info
     if let Some(rate) = detail.levels[&next_level].rate {
-        let info;
-        if next_level == 0 {
-            info = format!(
-                "Generates {:?} at the rate +{} / sec.",
-                detail.resource_type.unwrap(),
+        let info = if next_level == 0 {
+            format!(
+                "Generates {} at the rate +{} / sec.",
+                manifest.resource_name(detail.resource_type.as_deref().unwrap_or_default()),
                 rate
-            );
+            )
         } else {
-            info = format!("Next Level:+{}/sec", rate);
-        }
+            format!("Next Level:+{}/sec", rate)
+        };
+
+        ctx.print_color(
+            x,
+            y + y_offset,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            info,
+        );
+        y_offset += 1;
+    }
+    if let Some(capacity) = utils::capacity_for(detail, next_level) {
+        let info = if next_level == 0 {
+            format!("Adds storage: {}.", capacity_info(manifest, &capacity))
+        } else {
+            format!("Next Level:{}", capacity_info(manifest, &capacity))
+        };
+
+        ctx.print_color(
+            x,
+            y + y_offset,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            info,
+        );
+        y_offset += 1;
+    }
+
+    if let Some(converter) = utils::converter_for(detail, next_level) {
+        let info = if next_level == 0 {
+            format!(
+                "Converts {} into {} / sec.",
+                rates_info(manifest, &converter.inputs),
+                rates_info(manifest, &converter.outputs)
+            )
+        } else {
+            format!(
+                "Next Level:{} > {}",
+                rates_info(manifest, &converter.inputs),
+                rates_info(manifest, &converter.outputs)
+            )
+        };
 
         ctx.print_color(
             x,
@@ -637,8 +2814,33 @@ fn print_building_requirements(
         y_offset += 1;
     }
 
+    if let Some(upkeep) = &detail.levels[&next_level].upkeep {
+        ctx.print_color(
+            x,
+            y + y_offset,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!("Upkeep: {}/sec", rates_info(manifest, upkeep)),
+        );
+        y_offset += 1;
+    }
+
+    if let Some(training) = &detail.levels[&next_level].training {
+        ctx.print_color(
+            x,
+            y + y_offset,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!(
+                "Trains {} soldiers, {}s each",
+                training.max_garrison, training.seconds
+            ),
+        );
+        y_offset += 1;
+    }
+
     // requirements
-    if let Some(requirements) = detail.levels[&next_level].requirements {
+    if let Some(requirements) = &detail.levels[&next_level].requirements {
         if next_level == 0 {
             y_offset += 1;
             ctx.print_color(
@@ -655,53 +2857,32 @@ fn print_building_requirements(
         let mut color = RGB::named(rltk::WHITE);
         if let Some(cur_player_level) = requirements.current_player_level {
             level_req = format!("Player Level: {}", cur_player_level);
-
-            // #TODO
-            // check the current player level and decide the color
-            //
-        } else {
-            level_req = format!("Player Level:-");
-        }
-        ctx.print_color(x, y + y_offset, color, RGB::named(rltk::BLACK), level_req);
-        y_offset += 1;
-
-        let food_req;
-        color = RGB::named(rltk::WHITE);
-        if let Some(food) = requirements.food {
-            food_req = format!("Food: {}", food);
-            if player_stats.food.amount < food {
-                color = *utils::MORANDI_RED;
-            }
-        } else {
-            food_req = format!("Food:-");
-        }
-        ctx.print_color(x, y + y_offset, color, RGB::named(rltk::BLACK), food_req);
-        y_offset += 1;
-
-        let wood_req;
-        color = RGB::named(rltk::WHITE);
-        if let Some(wood) = requirements.wood {
-            wood_req = format!("Wood: {}", wood);
-            if player_stats.wood.amount < wood {
+            if player_stats.level < cur_player_level {
                 color = RGB::named(rltk::RED);
             }
         } else {
-            wood_req = format!("Wood:-");
+            level_req = "Player Level:-".to_string();
         }
-        ctx.print_color(x, y + y_offset, color, RGB::named(rltk::BLACK), wood_req);
+        ctx.print_color(x, y + y_offset, color, RGB::named(rltk::BLACK), level_req);
         y_offset += 1;
 
-        let stone_req;
-        color = RGB::named(rltk::WHITE);
-        if let Some(stone) = requirements.stone {
-            stone_req = format!("Stone: {}", stone);
-            if player_stats.stone.amount < stone {
-                color = RGB::named(rltk::RED);
+        // resources in the order the manifest declares them
+        for resource in manifest.resources.iter() {
+            if let Some(amount) = requirements.resources.get(&resource.id) {
+                color = if player_stats.amount(&resource.id) < *amount {
+                    RGB::named(rltk::RED)
+                } else {
+                    RGB::named(rltk::WHITE)
+                };
+                ctx.print_color(
+                    x,
+                    y + y_offset,
+                    color,
+                    RGB::named(rltk::BLACK),
+                    format!("{}: {}", resource.name, amount),
+                );
+                y_offset += 1;
             }
-        } else {
-            stone_req = format!("Stone:-");
         }
-        ctx.print_color(x, y + y_offset, color, RGB::named(rltk::BLACK), stone_req);
-        y_offset += 1;
     }
 }
@@ -0,0 +1,582 @@
+use rltk::VirtualKeyCode;
+use std::collections::HashMap;
+use std::fs;
+
+pub const KEY_BINDINGS_PATH: &str = "src/keybindings.json";
+
+/// Everything the player can do from the keyboard. Screens ask which of the
+/// actions they handle the pressed key is bound to, so one key may serve
+/// different actions on different screens.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum Action {
+    OpenBuildMenu,
+    SelectBuilding,
+    ShowBuildQueue,
+    SaveAndExit,
+    Pause,
+    Speed1,
+    Speed2,
+    Speed3,
+    Speed4,
+    ShowBindings,
+    ShowLog,
+    ShowStatistics,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+    MinimapJump,
+    CursorUp,
+    CursorDown,
+    CursorLeft,
+    CursorRight,
+    Confirm,
+    Cancel,
+    Upgrade,
+    FinishNow,
+    TearDown,
+    CancelBuild,
+    AddWorker,
+    RemoveWorker,
+    Yes,
+    No,
+}
+
+impl Action {
+    pub const ALL: [Action; 31] = [
+        Action::OpenBuildMenu,
+        Action::SelectBuilding,
+        Action::ShowBuildQueue,
+        Action::SaveAndExit,
+        Action::Pause,
+        Action::Speed1,
+        Action::Speed2,
+        Action::Speed3,
+        Action::Speed4,
+        Action::ShowBindings,
+        Action::ShowLog,
+        Action::ShowStatistics,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::ScrollLeft,
+        Action::ScrollRight,
+        Action::MinimapJump,
+        Action::CursorUp,
+        Action::CursorDown,
+        Action::CursorLeft,
+        Action::CursorRight,
+        Action::Confirm,
+        Action::Cancel,
+        Action::Upgrade,
+        Action::FinishNow,
+        Action::TearDown,
+        Action::CancelBuild,
+        Action::AddWorker,
+        Action::RemoveWorker,
+        Action::Yes,
+        Action::No,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::OpenBuildMenu => "Open the construction menu",
+            Action::SelectBuilding => "Select a building",
+            Action::ShowBuildQueue => "Show the build queue",
+            Action::SaveAndExit => "Save and return to the main menu",
+            Action::Pause => "Pause / resume",
+            Action::Speed1 => "Speed 1x",
+            Action::Speed2 => "Speed 2x",
+            Action::Speed3 => "Speed 5x",
+            Action::Speed4 => "Speed 10x",
+            Action::ShowBindings => "Show key bindings",
+            Action::ShowLog => "Show the message log",
+            Action::ShowStatistics => "Show resource statistics",
+            Action::ScrollUp => "Scroll up",
+            Action::ScrollDown => "Scroll down",
+            Action::ScrollLeft => "Scroll left",
+            Action::ScrollRight => "Scroll right",
+            Action::MinimapJump => "Jump to the minimap point under the mouse",
+            Action::CursorUp => "Cursor up",
+            Action::CursorDown => "Cursor down",
+            Action::CursorLeft => "Cursor left",
+            Action::CursorRight => "Cursor right",
+            Action::Confirm => "Confirm",
+            Action::Cancel => "Cancel / close",
+            Action::Upgrade => "Upgrade building",
+            Action::FinishNow => "Finish an upgrade at once",
+            Action::TearDown => "Tear down building",
+            Action::CancelBuild => "Cancel a queued construction",
+            Action::AddWorker => "Assign a worker",
+            Action::RemoveWorker => "Remove a worker",
+            Action::Yes => "Answer yes",
+            Action::No => "Answer no",
+        }
+    }
+
+    fn default_keys(&self) -> Vec<VirtualKeyCode> {
+        use VirtualKeyCode::*;
+
+        match self {
+            Action::OpenBuildMenu => vec![C],
+            Action::SelectBuilding => vec![S],
+            Action::ShowBuildQueue => vec![Q],
+            Action::SaveAndExit => vec![Escape],
+            Action::Pause => vec![Space],
+            Action::Speed1 => vec![Key1],
+            Action::Speed2 => vec![Key2],
+            Action::Speed3 => vec![Key3],
+            Action::Speed4 => vec![Key4],
+            Action::ShowBindings => vec![F1],
+            Action::ShowLog => vec![G],
+            Action::ShowStatistics => vec![I],
+            Action::ScrollUp => vec![Up],
+            Action::ScrollDown => vec![Down],
+            Action::ScrollLeft => vec![Left],
+            Action::ScrollRight => vec![Right],
+            Action::MinimapJump => vec![M],
+            Action::CursorUp => vec![K, Numpad8],
+            Action::CursorDown => vec![J, Numpad2],
+            Action::CursorLeft => vec![H, Numpad4],
+            Action::CursorRight => vec![L, Numpad6],
+            Action::Confirm => vec![Return, NumpadEnter],
+            Action::Cancel => vec![Escape],
+            Action::Upgrade => vec![U],
+            Action::FinishNow => vec![F],
+            Action::TearDown => vec![T],
+            Action::CancelBuild => vec![X],
+            Action::AddWorker => vec![Plus, Equals, NumpadAdd],
+            Action::RemoveWorker => vec![Minus, NumpadSubtract],
+            Action::Yes => vec![Y],
+            Action::No => vec![N],
+        }
+    }
+}
+
+/// Keys bound to every action.
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<VirtualKeyCode>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_keys()))
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, action: Action) -> &[VirtualKeyCode] {
+        self.bindings
+            .get(&action)
+            .map_or(&[], |keys| keys.as_slice())
+    }
+
+    pub fn pressed(&self, key: Option<VirtualKeyCode>, action: Action) -> bool {
+        key.is_some_and(|key| self.keys(action).contains(&key))
+    }
+
+    /// The first of `actions` the pressed key is bound to.
+    pub fn action(&self, key: Option<VirtualKeyCode>, actions: &[Action]) -> Option<Action> {
+        actions
+            .iter()
+            .find(|action| self.pressed(key, **action))
+            .copied()
+    }
+
+    /// Short name of the first key bound to `action`, for "[u] Upgrade" hints.
+    pub fn label(&self, action: Action) -> String {
+        self.keys(action)
+            .first()
+            .map_or("?".to_string(), |key| key_name(*key))
+    }
+}
+
+/// Reads the bindings file, a JSON object mapping action names to lists of
+/// key names. Actions left out of the file keep their default keys.
+pub fn load_key_bindings(path: &str) -> Result<KeyBindings, Vec<String>> {
+    let text =
+        fs::read_to_string(path).map_err(|e| vec![format!("cannot open {}: {}", path, e)])?;
+    parse_key_bindings(&text)
+}
+
+fn parse_key_bindings(text: &str) -> Result<KeyBindings, Vec<String>> {
+    let entries = serde_json::from_str::<HashMap<String, Vec<String>>>(text)
+        .map_err(|e| vec![format!("invalid JSON: {}", e)])?;
+
+    let mut bindings = KeyBindings::default();
+    let mut errors = Vec::new();
+    for (action_name, key_names) in entries.iter() {
+        let action = match Action::ALL
+            .iter()
+            .find(|action| format!("{:?}", action) == *action_name)
+        {
+            Some(action) => *action,
+            None => {
+                errors.push(format!("unknown action {}", action_name));
+                continue;
+            }
+        };
+        if key_names.is_empty() {
+            errors.push(format!("{} has no keys", action_name));
+            continue;
+        }
+
+        let mut keys = Vec::new();
+        for key_name in key_names.iter() {
+            match KEYS.iter().find(|key| format!("{:?}", key) == *key_name) {
+                Some(key) => keys.push(*key),
+                None => errors.push(format!("{}: unknown key {}", action_name, key_name)),
+            }
+        }
+        bindings.bindings.insert(action, keys);
+    }
+    errors.extend(conflicts(&bindings));
+
+    if !errors.is_empty() {
+        errors.sort();
+        return Err(errors);
+    }
+
+    Ok(bindings)
+}
+
+/// Actions read on every screen drawn over the map, which scrolls with them.
+const MAP_VIEW: &[Action] = &[
+    Action::ScrollUp,
+    Action::ScrollDown,
+    Action::ScrollLeft,
+    Action::ScrollRight,
+    Action::MinimapJump,
+];
+
+/// The actions each screen reads from the same key press, and whether it is
+/// drawn over the map. Within a screen no two actions may share a key.
+const SCREENS: &[(&str, bool, &[Action])] = &[
+    (
+        "main menu",
+        false,
+        &[Action::CursorUp, Action::CursorDown, Action::Confirm],
+    ),
+    ("offline summary", false, &[Action::Confirm, Action::Cancel]),
+    (
+        "map",
+        true,
+        &[
+            Action::OpenBuildMenu,
+            Action::SelectBuilding,
+            Action::ShowBuildQueue,
+            Action::SaveAndExit,
+            Action::ShowBindings,
+            Action::ShowLog,
+            Action::ShowStatistics,
+            Action::Pause,
+            Action::Speed1,
+            Action::Speed2,
+            Action::Speed3,
+            Action::Speed4,
+        ],
+    ),
+    (
+        "construction menu",
+        true,
+        &[
+            Action::Cancel,
+            Action::Confirm,
+            Action::CursorUp,
+            Action::CursorDown,
+        ],
+    ),
+    (
+        "construction spot",
+        true,
+        &[
+            Action::Cancel,
+            Action::Confirm,
+            Action::CursorUp,
+            Action::CursorDown,
+            Action::CursorLeft,
+            Action::CursorRight,
+        ],
+    ),
+    (
+        "building",
+        true,
+        &[
+            Action::Cancel,
+            Action::Upgrade,
+            Action::FinishNow,
+            Action::TearDown,
+            Action::AddWorker,
+            Action::RemoveWorker,
+            Action::CursorUp,
+            Action::CursorDown,
+            Action::CursorLeft,
+            Action::CursorRight,
+        ],
+    ),
+    (
+        "tear down",
+        true,
+        &[Action::Yes, Action::No, Action::Cancel],
+    ),
+    (
+        "build queue",
+        true,
+        &[
+            Action::Cancel,
+            Action::ShowBuildQueue,
+            Action::CancelBuild,
+            Action::CursorUp,
+            Action::CursorDown,
+        ],
+    ),
+    (
+        "key bindings",
+        false,
+        &[Action::Cancel, Action::ShowBindings],
+    ),
+    (
+        "message log",
+        false,
+        &[
+            Action::Cancel,
+            Action::ShowLog,
+            Action::CursorUp,
+            Action::ScrollUp,
+            Action::CursorDown,
+            Action::ScrollDown,
+        ],
+    ),
+    (
+        "statistics",
+        false,
+        &[
+            Action::Cancel,
+            Action::ShowStatistics,
+            Action::CursorLeft,
+            Action::ScrollLeft,
+            Action::CursorRight,
+            Action::ScrollRight,
+            Action::CursorUp,
+            Action::ScrollUp,
+            Action::CursorDown,
+            Action::ScrollDown,
+        ],
+    ),
+];
+
+/// Keys bound to two actions that the same screen reads.
+fn conflicts(bindings: &KeyBindings) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (screen, over_map, actions) in SCREENS.iter() {
+        let mut read: Vec<Action> = actions.to_vec();
+        if *over_map {
+            read.extend_from_slice(MAP_VIEW);
+        }
+
+        let mut seen: HashMap<VirtualKeyCode, Action> = HashMap::new();
+        for action in read {
+            for key in bindings.keys(action) {
+                match seen.get(key) {
+                    Some(other) if *other != action => errors.push(format!(
+                        "{:?} is bound to both {:?} and {:?} on the {} screen",
+                        key, other, action, screen
+                    )),
+                    _ => {
+                        seen.insert(*key, action);
+                    }
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+/// How a key is shown in hints, e.g. "u", "1", "+", "num8", "enter".
+pub fn key_name(key: VirtualKeyCode) -> String {
+    let name = format!("{:?}", key);
+    match key {
+        VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => "+".to_string(),
+        VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => "-".to_string(),
+        VirtualKeyCode::Equals => "=".to_string(),
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => "enter".to_string(),
+        VirtualKeyCode::Escape => "esc".to_string(),
+        _ if name.len() == 1 => name.to_lowercase(),
+        _ if name.starts_with("Key") => name[3..].to_string(),
+        _ if name.starts_with("Numpad") => format!("num{}", &name[6..]).to_lowercase(),
+        _ if name.starts_with('F') && name[1..].parse::<u32>().is_ok() => name,
+        _ => name.to_lowercase(),
+    }
+}
+
+/// Keys the bindings file may name, spelled as in `VirtualKeyCode`.
+const KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+
+    &[
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Key0,
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadAdd,
+        NumpadSubtract,
+        NumpadMultiply,
+        NumpadDivide,
+        NumpadDecimal,
+        NumpadEnter,
+        Up,
+        Down,
+        Left,
+        Right,
+        Return,
+        Escape,
+        Space,
+        Tab,
+        Back,
+        Delete,
+        Insert,
+        Home,
+        End,
+        PageUp,
+        PageDown,
+        Minus,
+        Equals,
+        Plus,
+        Comma,
+        Period,
+        Slash,
+        Backslash,
+        Semicolon,
+        Apostrophe,
+        LBracket,
+        RBracket,
+        Grave,
+    ]
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_file_matches_the_defaults() {
+        let text = fs::read_to_string(KEY_BINDINGS_PATH).unwrap();
+        let entries: HashMap<String, Vec<String>> = serde_json::from_str(&text).unwrap();
+        assert_eq!(entries.len(), Action::ALL.len());
+
+        let shipped = load_key_bindings(KEY_BINDINGS_PATH).unwrap();
+        for action in Action::ALL.iter() {
+            assert_eq!(
+                shipped.keys(*action),
+                action.default_keys().as_slice(),
+                "{:?}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        assert!(conflicts(&KeyBindings::default()).is_empty());
+    }
+
+    #[test]
+    fn bindings_override_the_defaults() {
+        let bindings = parse_key_bindings(r#"{ "Upgrade": ["B", "F5"] }"#).unwrap();
+        assert_eq!(
+            bindings.keys(Action::Upgrade),
+            &[VirtualKeyCode::B, VirtualKeyCode::F5]
+        );
+        assert_eq!(bindings.keys(Action::TearDown), &[VirtualKeyCode::T]);
+    }
+
+    #[test]
+    fn unknown_names_and_empty_bindings_are_errors() {
+        let errors = parse_key_bindings(r#"{ "Fly": ["A"], "Upgrade": ["Banana"], "No": [] }"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors,
+            vec![
+                "No has no keys".to_string(),
+                "Upgrade: unknown key Banana".to_string(),
+                "unknown action Fly".to_string(),
+            ]
+        );
+        assert!(parse_key_bindings("[").is_err());
+    }
+
+    #[test]
+    fn a_key_shared_by_one_screen_is_an_error() {
+        let errors = parse_key_bindings(r#"{ "Upgrade": ["T"] }"#).err().unwrap();
+        assert_eq!(
+            errors,
+            vec!["T is bound to both Upgrade and TearDown on the building screen".to_string()]
+        );
+
+        // the map view scrolls while the building screen is open
+        assert!(parse_key_bindings(r#"{ "Upgrade": ["Up"] }"#).is_err());
+
+        // pausing on the map and answering yes elsewhere may share a key
+        assert!(parse_key_bindings(r#"{ "Pause": ["Y"] }"#).is_ok());
+    }
+}
@@ -1,80 +1,307 @@
-use chrono::prelude::*;
-use map::draw_map;
-use render::draw_buildings;
+use construction_system::ConstructionSystem;
+use gamelog::LogCategory;
+use military_system::{CombatSystem, RaidSystem, TrainingSystem};
+use render::{draw_buildings, draw_construction_sites, draw_units};
 use resource_system::ResourceSystem;
-use rltk::{GameState, Point, Rltk, RGB};
+use rltk::{GameState, Rltk};
+use saveload_system::SaveNotice;
 use specs::prelude::*;
 use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
-use std::collections::{hash_map, HashMap};
-use std::time::Duration;
-
-use crate::map::Map;
-use rand::Rng;
-
-// serde
-use serde::{Deserialize, Serialize};
-
-use std::error::Error;
-use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
 
+mod camera;
+mod clock;
 mod components;
+mod construction_system;
+mod manifest;
 mod map;
+mod map_builder;
+mod progression;
+pub use camera::*;
+pub use clock::*;
 pub use components::*;
+pub use manifest::*;
 pub use map::*;
 
 mod gui;
 mod rect;
 pub use rect::*;
 mod control;
+mod deposits;
+mod gamelog;
+mod keybindings;
+mod military_system;
 mod render;
 mod resource_system;
+mod saveload_system;
 mod spawner;
 mod utils;
 
+#[cfg(test)]
+mod test_support;
+
 pub const WINDOW_HEIGHT: usize = 100;
 pub const WINDOW_WIDTH: usize = 150;
+pub const AUTOSAVE_INTERVAL: i64 = 60; // second
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum RunState {
+    MainMenu {
+        selected: gui::MainMenuSelection,
+    },
+    SaveGame,
+    OfflineSummary,
     PreRun,
     Idle,
-    ConstructionMenu { selected_idx: usize },
-    ConstructionSpotSelecting { selected_idx: usize, x: i32, y: i32 },
-    ConstructionSelecting { x: i32, y: i32 },
+    ConstructionMenu {
+        selected_idx: usize,
+    },
+    ConstructionSpotSelecting {
+        selected_idx: usize,
+        x: i32,
+        y: i32,
+    },
+    ConstructionSelecting {
+        x: i32,
+        y: i32,
+    },
+    ConfirmTearDown {
+        x: i32,
+        y: i32,
+    },
+    BuildQueue {
+        selected_idx: usize,
+    },
+    KeyBindings,
+    MessageLog {
+        scroll: usize,
+    },
+    Statistics {
+        resource_idx: usize,
+        long_term: bool,
+    },
 }
 
 pub struct State {
     pub ecs: World,
+    pub next_autosave: i64, // second
 }
 
 impl State {
+    fn new_game(&mut self) {
+        let to_delete: Vec<Entity> = self.ecs.entities().join().collect();
+        for entity in to_delete {
+            self.ecs.delete_entity(entity).expect("Deletion failed");
+        }
+
+        let player = spawner::player(&mut self.ecs);
+        let seed = self
+            .ecs
+            .write_resource::<rltk::RandomNumberGenerator>()
+            .next_u64();
+        *self.ecs.write_resource::<Map>() = map_builder::build_map(seed);
+        *self.ecs.write_resource::<Entity>() = player;
+        self.reset_clock();
+        let current = self.ecs.fetch::<GameClock>().now();
+        *self.ecs.write_resource::<gamelog::GameLog>() = gamelog::GameLog::new(current);
+        *self.ecs.write_resource::<Camera>() = Camera::new();
+    }
+
+    /// A new or loaded world starts running at normal speed.
+    fn reset_clock(&mut self) {
+        let mut clock = self.ecs.write_resource::<GameClock>();
+        clock.set_paused(false);
+        clock.set_speed(1);
+    }
+
+    /// Points the camera at the first building, or the middle of the map.
+    fn center_camera_on_settlement(&mut self) {
+        let center = {
+            let buildings = self.ecs.read_storage::<Building>();
+            buildings
+                .join()
+                .next()
+                .map(|building| building.rect.center())
+        };
+        let mut camera = self.ecs.write_resource::<Camera>();
+        *camera = Camera::new();
+        if let Some((x, y)) = center {
+            camera.center_on(x, y);
+        }
+    }
+
+    /// Scrolls the building at a selection cursor into view.
+    fn follow_building_at(&mut self, x: i32, y: i32) {
+        if let Some(entity) = utils::building_at(&self.ecs, x, y) {
+            let rect = self
+                .ecs
+                .read_storage::<Building>()
+                .get(entity)
+                .unwrap()
+                .rect;
+            self.ecs.write_resource::<Camera>().follow(&rect);
+        }
+    }
+
+    fn follow_site(&mut self, queue_idx: usize) {
+        let queue = construction_system::build_queue(&self.ecs);
+        if let Some(entity) = queue.get(queue_idx) {
+            let rect = self
+                .ecs
+                .read_storage::<ConstructionSite>()
+                .get(*entity)
+                .unwrap()
+                .rect;
+            self.ecs.write_resource::<Camera>().follow(&rect);
+        }
+    }
+
+    /// Records why the building picked in the construction menu can't be built,
+    /// and returns whether it can't.
+    fn log_unaffordable(&mut self, selected_idx: usize) -> bool {
+        let problem = {
+            let manifest = self.ecs.fetch::<ConstructionManifest>();
+            let detail = &manifest.buildings[selected_idx];
+            let player = *self.ecs.fetch::<Entity>();
+            let stats_storage = self.ecs.read_storage::<PlayerStats>();
+            let player_stats = stats_storage.get(player).unwrap();
+            utils::requirements_problem(player_stats, None, detail, 0, &manifest)
+                .map(|problem| format!("Can't build a {}: {}.", detail.name, problem))
+        };
+        match problem {
+            Some(problem) => {
+                gamelog::log(&self.ecs, LogCategory::Failure, problem);
+                true
+            }
+            None => false,
+        }
+    }
+
     fn run_systems(&mut self) {
         let mut resource = ResourceSystem {};
+        let mut training = TrainingSystem {};
+        let mut raid = RaidSystem {};
+        let mut combat = CombatSystem {};
+        let mut construction = ConstructionSystem {};
 
         resource.run_now(&self.ecs);
+        training.run_now(&self.ecs);
+        raid.run_now(&self.ecs);
+        combat.run_now(&self.ecs);
+        construction.run_now(&self.ecs);
 
         self.ecs.maintain();
+        military_system::apply_damage(&mut self.ecs);
+        construction_system::finish_construction(&mut self.ecs);
+        construction_system::finish_upgrades(&mut self.ecs);
     }
 }
 
 impl GameState for State {
     fn tick(&mut self, ctx: &mut Rltk) {
         ctx.cls();
-        draw_map(&self.ecs, ctx);
-        draw_buildings(&self.ecs, ctx);
-        gui::draw_ui(&self.ecs, ctx);
+        self.ecs.write_resource::<GameClock>().tick();
 
-        // state machine
+        control::track_mouse(&mut self.ecs, ctx);
         let mut new_runstate = *self.ecs.fetch::<RunState>();
         match new_runstate {
+            RunState::MainMenu { .. } => {}
+            _ => {
+                manifest::poll_manifest(&mut self.ecs);
+                // the full-screen panels use the scroll keys themselves
+                if matches!(
+                    new_runstate,
+                    RunState::Idle
+                        | RunState::ConstructionMenu { .. }
+                        | RunState::ConstructionSpotSelecting { .. }
+                        | RunState::ConstructionSelecting { .. }
+                        | RunState::ConfirmTearDown { .. }
+                        | RunState::BuildQueue { .. }
+                ) {
+                    control::scroll_camera(&mut self.ecs, ctx);
+                }
+                draw_map(&self.ecs, ctx);
+                draw_buildings(&self.ecs, ctx);
+                draw_construction_sites(&self.ecs, ctx);
+                draw_units(&self.ecs, ctx);
+                gui::draw_ui(&self.ecs, ctx);
+            }
+        }
+
+        // state machine
+        match new_runstate {
+            RunState::MainMenu { .. } => {
+                let result = gui::draw_main_menu(&mut self.ecs, ctx);
+                match result {
+                    gui::MainMenuResult::NoSelection { selected } => {
+                        new_runstate = RunState::MainMenu { selected }
+                    }
+                    gui::MainMenuResult::Selected { selected } => match selected {
+                        gui::MainMenuSelection::NewGame => {
+                            self.ecs.write_resource::<SaveNotice>().notice = None;
+                            self.new_game();
+                            new_runstate = RunState::PreRun;
+                        }
+                        gui::MainMenuSelection::LoadGame => {
+                            match saveload_system::load_game(&mut self.ecs) {
+                                Ok(()) => {
+                                    self.ecs.write_resource::<SaveNotice>().notice = None;
+                                    self.reset_clock();
+                                    self.center_camera_on_settlement();
+                                    let report =
+                                        resource_system::apply_offline_progress(&mut self.ecs);
+                                    if report.elapsed > 0 {
+                                        self.ecs.insert(report);
+                                        new_runstate = RunState::OfflineSummary;
+                                    } else {
+                                        new_runstate = RunState::PreRun;
+                                    }
+                                }
+                                Err(error) => {
+                                    self.ecs.write_resource::<SaveNotice>().notice =
+                                        Some(format!("Unable to load the game: {}", error));
+                                    new_runstate = RunState::MainMenu { selected };
+                                }
+                            }
+                        }
+                        gui::MainMenuSelection::Quit => std::process::exit(0),
+                    },
+                }
+            }
+            RunState::SaveGame => {
+                self.ecs.write_resource::<SaveNotice>().notice =
+                    saveload_system::save_game(&mut self.ecs)
+                        .err()
+                        .map(|error| format!("Unable to save the game: {}", error));
+                self.next_autosave = WallClock.now() + AUTOSAVE_INTERVAL;
+                new_runstate = RunState::MainMenu {
+                    selected: gui::MainMenuSelection::LoadGame,
+                };
+            }
+            RunState::OfflineSummary => {
+                let result = gui::draw_offline_summary(&mut self.ecs, ctx);
+                match result {
+                    gui::OfflineSummaryResult::NoResponse => {}
+                    gui::OfflineSummaryResult::Dismiss => new_runstate = RunState::PreRun,
+                }
+            }
             RunState::PreRun => {
                 self.run_systems();
+                self.next_autosave = WallClock.now() + AUTOSAVE_INTERVAL;
                 new_runstate = RunState::Idle;
             }
             RunState::Idle => {
                 self.run_systems();
+                if WallClock.now() >= self.next_autosave {
+                    if let Err(error) = saveload_system::save_game(&mut self.ecs) {
+                        gamelog::log(
+                            &self.ecs,
+                            LogCategory::Failure,
+                            format!("Autosave failed: {}", error),
+                        );
+                    }
+                    self.next_autosave = WallClock.now() + AUTOSAVE_INTERVAL;
+                }
+                gui::draw_tooltips(&self.ecs, ctx);
                 new_runstate = control::player_input(&mut self.ecs, ctx);
             }
             RunState::ConstructionMenu { .. } => {
@@ -90,15 +317,16 @@ impl GameState for State {
                         // let rect = Rect::new(x, y, 4, 4);
                         // spawner::spawn_mill(&mut self.ecs, rect);
                         // new_runstate = RunState::Idle
-                        new_runstate = RunState::ConstructionSpotSelecting {
-                            selected_idx,
-                            x: WINDOW_WIDTH as i32 / 2,
-                            y: WINDOW_HEIGHT as i32 / 2,
-                        };
+                        let (x, y) = self.ecs.fetch::<Camera>().center();
+                        new_runstate = RunState::ConstructionSpotSelecting { selected_idx, x, y };
                     }
                     gui::ConstructionMenuResult::NoSelection { selected_idx } => {
                         new_runstate = RunState::ConstructionMenu { selected_idx }
                     }
+                    gui::ConstructionMenuResult::Unaffordable { selected_idx } => {
+                        self.log_unaffordable(selected_idx);
+                        new_runstate = RunState::ConstructionMenu { selected_idx }
+                    }
                     gui::ConstructionMenuResult::Escape => new_runstate = RunState::Idle,
                 }
             }
@@ -107,20 +335,30 @@ impl GameState for State {
                 let result = gui::draw_construction_spot(&mut self.ecs, ctx);
                 match result {
                     gui::ConstructionSpotSelectingResult::Selected { selected_idx, x, y } => {
-                        let (detail, spawner_fn) =
-                            spawner::get_spawner(&mut self.ecs, selected_idx);
-
-                        {
-                            let player = *self.ecs.fetch::<Entity>();
-                            let mut stats_storage = self.ecs.write_storage::<PlayerStats>();
-                            let player_stats = stats_storage.get_mut(player).unwrap();
-                            utils::consume_resource(player_stats, &detail, 0);
-                        }
+                        // something may have been spent while the spot was picked
+                        if self.log_unaffordable(selected_idx) {
+                            new_runstate = RunState::ConstructionMenu { selected_idx };
+                        } else {
+                            let detail = self.ecs.fetch::<ConstructionManifest>().buildings
+                                [selected_idx]
+                                .clone();
+
+                            {
+                                let player = *self.ecs.fetch::<Entity>();
+                                let mut stats_storage = self.ecs.write_storage::<PlayerStats>();
+                                let player_stats = stats_storage.get_mut(player).unwrap();
+                                utils::consume_resource(player_stats, &detail, 0);
+                            }
 
-                        spawner_fn(&mut self.ecs, detail, x, y);
-                        new_runstate = RunState::Idle
+                            spawner::spawn_construction_site(&mut self.ecs, &detail, x, y);
+                            new_runstate = RunState::Idle
+                        }
                     }
                     gui::ConstructionSpotSelectingResult::NoSelection { selected_idx, x, y } => {
+                        let detail =
+                            &self.ecs.fetch::<ConstructionManifest>().buildings[selected_idx];
+                        let spot = Rect::new(x, y, detail.width, detail.height);
+                        self.ecs.write_resource::<Camera>().follow(&spot);
                         new_runstate = RunState::ConstructionSpotSelecting { selected_idx, x, y }
                     }
                     gui::ConstructionSpotSelectingResult::Escape => {
@@ -128,14 +366,43 @@ impl GameState for State {
                     }
                 }
             }
-            RunState::ConstructionSelecting { x, y } => {
+            RunState::ConstructionSelecting { .. } => {
                 self.run_systems();
                 let result = gui::draw_construction_selecting(&mut self.ecs, ctx);
                 match result {
-                    gui::ConstructionSelectingResult::Selected { .. } => {
-                        new_runstate = RunState::Idle;
+                    gui::ConstructionSelectingResult::Upgrade { x, y } => {
+                        if let Some(entity) = utils::building_at(&self.ecs, x, y) {
+                            match utils::upgrade_building(&mut self.ecs, entity) {
+                                Ok(()) => resource_system::refresh_rates(&mut self.ecs),
+                                Err(reason) => {
+                                    gamelog::log(&self.ecs, LogCategory::Failure, reason)
+                                }
+                            }
+                        }
+                        new_runstate = RunState::ConstructionSelecting { x, y };
+                    }
+                    gui::ConstructionSelectingResult::FinishNow { x, y } => {
+                        if let Some(entity) = utils::building_at(&self.ecs, x, y) {
+                            if let Err(reason) = utils::finish_upgrade_now(&mut self.ecs, entity) {
+                                gamelog::log(&self.ecs, LogCategory::Failure, reason);
+                            }
+                        }
+                        new_runstate = RunState::ConstructionSelecting { x, y };
+                    }
+                    gui::ConstructionSelectingResult::AssignWorker { x, y, delta } => {
+                        if let Some(entity) = utils::building_at(&self.ecs, x, y) {
+                            utils::assign_workers(&mut self.ecs, entity, delta);
+                            resource_system::refresh_rates(&mut self.ecs);
+                        }
+                        new_runstate = RunState::ConstructionSelecting { x, y };
+                    }
+                    gui::ConstructionSelectingResult::TearDown { x, y } => {
+                        new_runstate = RunState::ConfirmTearDown { x, y };
                     }
                     gui::ConstructionSelectingResult::NoSelection { x, y } => {
+                        if new_runstate != (RunState::ConstructionSelecting { x, y }) {
+                            self.follow_building_at(x, y);
+                        }
                         new_runstate = RunState::ConstructionSelecting { x, y };
                     }
                     gui::ConstructionSelectingResult::Escape => {
@@ -143,6 +410,74 @@ impl GameState for State {
                     }
                 }
             }
+            RunState::ConfirmTearDown { x, y } => {
+                self.run_systems();
+                let result = gui::draw_tear_down_confirm(&mut self.ecs, ctx);
+                match result {
+                    gui::TearDownConfirmResult::NoResponse => {}
+                    gui::TearDownConfirmResult::Cancel => {
+                        new_runstate = RunState::ConstructionSelecting { x, y };
+                    }
+                    gui::TearDownConfirmResult::Confirm { entity } => {
+                        utils::tear_down_building(&mut self.ecs, entity);
+                        new_runstate = RunState::ConstructionSelecting { x: 0, y: 0 };
+                    }
+                }
+            }
+            RunState::BuildQueue { .. } => {
+                self.run_systems();
+                let result = gui::draw_build_queue(&mut self.ecs, ctx);
+                match result {
+                    gui::BuildQueueResult::Close => new_runstate = RunState::Idle,
+                    gui::BuildQueueResult::NoSelection { selected_idx } => {
+                        if new_runstate != (RunState::BuildQueue { selected_idx }) {
+                            self.follow_site(selected_idx);
+                        }
+                        new_runstate = RunState::BuildQueue { selected_idx };
+                    }
+                    gui::BuildQueueResult::Cancel {
+                        entity,
+                        selected_idx,
+                    } => {
+                        utils::cancel_construction(&mut self.ecs, entity);
+                        new_runstate = RunState::BuildQueue { selected_idx };
+                    }
+                }
+            }
+            RunState::KeyBindings => {
+                self.run_systems();
+                let result = gui::draw_key_bindings(&mut self.ecs, ctx);
+                match result {
+                    gui::KeyBindingsResult::NoResponse => {}
+                    gui::KeyBindingsResult::Close => new_runstate = RunState::Idle,
+                }
+            }
+            RunState::MessageLog { .. } => {
+                self.run_systems();
+                let result = gui::draw_message_log(&mut self.ecs, ctx);
+                match result {
+                    gui::MessageLogResult::Close => new_runstate = RunState::Idle,
+                    gui::MessageLogResult::NoSelection { scroll } => {
+                        new_runstate = RunState::MessageLog { scroll }
+                    }
+                }
+            }
+            RunState::Statistics { .. } => {
+                self.run_systems();
+                let result = gui::draw_statistics(&mut self.ecs, ctx);
+                match result {
+                    gui::StatisticsResult::Close => new_runstate = RunState::Idle,
+                    gui::StatisticsResult::NoSelection {
+                        resource_idx,
+                        long_term,
+                    } => {
+                        new_runstate = RunState::Statistics {
+                            resource_idx,
+                            long_term,
+                        }
+                    }
+                }
+            }
         }
 
         let mut runstate_writer = self.ecs.write_resource::<RunState>();
@@ -150,86 +485,91 @@ impl GameState for State {
     }
 }
 
-// Constrction list
-#[derive(Deserialize, Debug)]
-pub struct ConstructionManifest {
-    pub buildings: Vec<BuildingDetail>,
+fn register_components(ecs: &mut World) {
+    ecs.register::<PlayerStats>();
+    ecs.register::<ResourceHistory>();
+    ecs.register::<Generator>();
+    ecs.register::<Capacity>();
+    ecs.register::<Converter>();
+    ecs.register::<Housing>();
+    ecs.register::<Workforce>();
+    ecs.register::<Upkeep>();
+    ecs.register::<Garrison>();
+    ecs.register::<ConstructionSite>();
+    ecs.register::<Renovation>();
+    ecs.register::<Position>();
+    ecs.register::<Raider>();
+    ecs.register::<RaidSchedule>();
+    ecs.register::<WantsToRaid>();
+    ecs.register::<SufferDamage>();
+    ecs.register::<Renderable>();
+    ecs.register::<Building>();
+    ecs.register::<Name>();
+    ecs.register::<SimpleMarker<SerializeMe>>();
+    ecs.register::<SerializationHelper>();
 }
 
-#[derive(Deserialize, Clone, Debug)]
-pub struct BuildingDetail {
-    pub name: String,
-    pub width: i32,
-    pub height: i32,
-    pub fg: String,
-    pub bg: String,
-    pub glyph: char,
-    pub resource_type: Option<ResourceType>,
-    pub levels: HashMap<i32, LevelDetail>,
-}
+fn main() -> rltk::BError {
+    use rltk::RltkBuilder;
 
-#[derive(Deserialize, Copy, Clone, Debug)]
-pub struct LevelDetail {
-    pub rate: Option<i32>,
-    pub requirements: Option<ConstructionRequirment>,
-}
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|arg| arg == "--check-manifest") {
+        let path = args
+            .get(pos + 1)
+            .map_or(MANIFEST_PATH, |path| path.as_str());
+        std::process::exit(manifest::check_manifest(path));
+    }
 
-#[derive(Deserialize, Copy, Clone, Debug)]
-pub struct ConstructionRequirment {
-    pub current_player_level: Option<i32>,
-    pub current_building_level: Option<i32>,
-    pub food: Option<i32>,
-    pub wood: Option<i32>,
-    pub stone: Option<i32>,
-}
+    let construction_manifest = match manifest::load_manifest(MANIFEST_PATH) {
+        Ok(construction_manifest) => construction_manifest,
+        Err(errors) => {
+            manifest::report_errors(MANIFEST_PATH, &errors);
+            std::process::exit(1);
+        }
+    };
 
-#[derive(PartialEq, Serialize, Deserialize, Copy, Clone, Debug)]
-pub enum ResourceType {
-    Food,
-    Stone,
-    Wood,
-}
+    let level_table = match progression::load_level_table(progression::LEVEL_TABLE_PATH) {
+        Ok(level_table) => level_table,
+        Err(errors) => {
+            manifest::report_errors(progression::LEVEL_TABLE_PATH, &errors);
+            std::process::exit(1);
+        }
+    };
+
+    let key_bindings = match keybindings::load_key_bindings(keybindings::KEY_BINDINGS_PATH) {
+        Ok(key_bindings) => key_bindings,
+        Err(errors) => {
+            manifest::report_errors(keybindings::KEY_BINDINGS_PATH, &errors);
+            std::process::exit(1);
+        }
+    };
 
-fn main() -> rltk::BError {
-    use rltk::RltkBuilder;
     let context = RltkBuilder::simple(WINDOW_WIDTH, WINDOW_HEIGHT)
         .expect("Failed creating window")
         .with_title("Aurorian")
         .build()?;
-    let mut gs = State { ecs: World::new() };
-
-    gs.ecs.register::<PlayerStats>();
-    gs.ecs.register::<Generator>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<Building>();
-    gs.ecs.register::<Name>();
-
-    let map = Map::new();
-
-    // create player
-    let current: DateTime<Local> = Local::now();
-    let player = gs
-        .ecs
-        .create_entity()
-        .with(PlayerStats {
-            food: ResourceInfo {
-                amount: 0,
-                max_amount: 10000,
-                rate: 0,
-            },
-            wood: ResourceInfo {
-                amount: 0,
-                max_amount: 10000,
-                rate: 0,
-            },
-            stone: ResourceInfo {
-                amount: 0,
-                max_amount: 10000,
-                rate: 0,
-            },
-            next_refresh: current.timestamp() - 1,
-        })
-        .build();
+    let mut gs = State {
+        ecs: World::new(),
+        next_autosave: 0,
+    };
+
+    register_components(&mut gs.ecs);
+
+    gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+    // `--fixed-step <millis>` advances the game by a set amount every frame
+    // instead of following real time
+    let fixed_step = args
+        .iter()
+        .position(|arg| arg == "--fixed-step")
+        .and_then(|pos| args.get(pos + 1))
+        .and_then(|step| step.parse::<i64>().ok());
+    match fixed_step {
+        Some(step) => gs.ecs.insert(GameClock::new(ManualClock::new(
+            WallClock.now_millis(),
+            step,
+        ))),
+        None => gs.ecs.insert(GameClock::new(ScaledClock::new())),
+    }
 
     // mill
     // gs.ecs
@@ -246,14 +586,24 @@ fn main() -> rltk::BError {
     //     .with(FoodGenerator { rate: 2 })
     //     .build();
 
-    let file = File::open("src/constructions.json")?;
-    let reader = BufReader::new(file);
-    let construction_manifest = serde_json::from_reader::<_, ConstructionManifest>(reader)?;
-
     gs.ecs.insert(construction_manifest);
-    gs.ecs.insert(map);
+    gs.ecs.insert(ManifestWatcher::new(MANIFEST_PATH));
+    gs.ecs.insert(level_table);
+    gs.ecs.insert(key_bindings);
+    gs.ecs.insert(Map::new());
+    gs.ecs.insert(Camera::new());
+    gs.ecs.insert(control::MouseCursor::default());
+    let current = gs.ecs.fetch::<GameClock>().now();
+    gs.ecs.insert(gamelog::GameLog::new(current));
+    gs.ecs.insert(SaveNotice::default());
+    let player = spawner::player(&mut gs.ecs);
     gs.ecs.insert(player);
-    gs.ecs.insert(RunState::PreRun);
+    let selected = if saveload_system::does_save_exist() {
+        gui::MainMenuSelection::LoadGame
+    } else {
+        gui::MainMenuSelection::NewGame
+    };
+    gs.ecs.insert(RunState::MainMenu { selected });
     gs.ecs.insert(rltk::RandomNumberGenerator::new());
 
     rltk::main_loop(context, gs)
@@ -0,0 +1,955 @@
+use serde::Deserialize;
+use specs::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{utils, Building, ConstructionSite, Name, Renovation, TileType, MAP_HEIGHT, MAP_WIDTH};
+
+pub const MANIFEST_PATH: &str = "src/constructions.json";
+pub const MANIFEST_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Constrction list
+#[derive(Deserialize, Debug)]
+pub struct ConstructionManifest {
+    pub resources: Vec<ResourceDetail>,
+    pub buildings: Vec<BuildingDetail>,
+    #[serde(default)]
+    pub offline: OfflineSettings,
+    #[serde(default)]
+    pub population: PopulationSettings,
+    #[serde(default)]
+    pub raids: RaidSettings,
+    #[serde(default)]
+    pub upgrades: UpgradeSettings,
+    /// Finite resources lying on the map, e.g. wood in forests.
+    #[serde(default)]
+    pub deposits: Vec<DepositDetail>,
+    /// Percent of everything spent on a building that tearing it down returns.
+    #[serde(default = "ConstructionManifest::default_tear_down_refund")]
+    pub tear_down_refund: i32,
+    /// Construction sites worked on at the same time; the rest wait in the queue.
+    #[serde(default = "ConstructionManifest::default_builder_slots")]
+    pub builder_slots: i32,
+}
+
+impl ConstructionManifest {
+    fn default_tear_down_refund() -> i32 {
+        50
+    }
+
+    fn default_builder_slots() -> i32 {
+        1
+    }
+
+    pub fn resource(&self, id: &str) -> Option<&ResourceDetail> {
+        self.resources.iter().find(|r| r.id == id)
+    }
+
+    /// Display name of a resource, falling back to its id.
+    pub fn resource_name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.resource(id).map_or(id, |r| r.name.as_str())
+    }
+
+    pub fn building(&self, name: &str) -> Option<&BuildingDetail> {
+        self.buildings.iter().find(|b| b.name == name)
+    }
+
+    pub fn deposit(&self, tile: TileType) -> Option<&DepositDetail> {
+        self.deposits.iter().find(|d| d.tile == tile)
+    }
+}
+
+/// Identifier a resource is declared with in the manifest, e.g. "food".
+pub type ResourceType = String;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ResourceDetail {
+    pub id: ResourceType,
+    pub name: String,
+    pub fg: String,
+    #[serde(default)]
+    pub starting_amount: i32,
+    /// Storage cap before storage buildings are added.
+    pub base_cap: i32,
+}
+
+/// How resources accrue while the game is closed.
+#[derive(Deserialize, Copy, Clone, Debug)]
+pub struct OfflineSettings {
+    pub max_seconds: Option<i64>,
+    #[serde(default = "OfflineSettings::full_efficiency")]
+    pub efficiency: i32, // percent of the online rate
+}
+
+impl OfflineSettings {
+    fn full_efficiency() -> i32 {
+        100
+    }
+}
+
+impl Default for OfflineSettings {
+    fn default() -> Self {
+        OfflineSettings {
+            max_seconds: None,
+            efficiency: OfflineSettings::full_efficiency(),
+        }
+    }
+}
+
+/// How the settlement's population grows into the available housing.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PopulationSettings {
+    pub starting: i32,
+    /// Housing available before any housing building is added.
+    pub base_housing: i32,
+    /// Seconds between two new citizens while there is room and food.
+    pub growth_seconds: i32,
+    /// Resource a new citizen eats to join the settlement, e.g. "food".
+    pub food: ResourceType,
+    pub growth_food: i32,
+    /// Citizens one unit of food per second feeds.
+    pub citizens_per_food: i32,
+    /// Percent of their rate generators keep while the settlement starves.
+    pub starvation_penalty: i32,
+    /// Seconds without food before citizens start leaving.
+    pub starvation_grace_seconds: i32,
+    /// Seconds between two citizens leaving once the grace period is over.
+    pub starvation_loss_seconds: i32,
+}
+
+impl Default for PopulationSettings {
+    fn default() -> Self {
+        PopulationSettings {
+            starting: 5,
+            base_housing: 10,
+            growth_seconds: 20,
+            food: "food".to_string(),
+            growth_food: 50,
+            citizens_per_food: 5,
+            starvation_penalty: 50,
+            starvation_grace_seconds: 30,
+            starvation_loss_seconds: 10,
+        }
+    }
+}
+
+/// Every `tile` on the map holds `amount` of `resource`. Once harvested it
+/// turns into `depleted_tile`, and grows back after `regrow_seconds` if set.
+#[derive(Deserialize, Clone, Debug)]
+pub struct DepositDetail {
+    pub tile: TileType,
+    pub resource: ResourceType,
+    pub amount: i32,
+    pub depleted_tile: TileType,
+    pub regrow_seconds: Option<i32>,
+}
+
+/// How buildings behave while they are being upgraded.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct UpgradeSettings {
+    /// Percent of the usual rate a generator keeps during an upgrade.
+    /// Converters stand still either way.
+    pub production_percent: i32,
+    /// Resource spent to finish an upgrade at once, if any.
+    pub instant_resource: Option<ResourceType>,
+    /// Paid for every started minute the upgrade still has left.
+    pub instant_cost_per_minute: i32,
+}
+
+impl Default for UpgradeSettings {
+    fn default() -> Self {
+        UpgradeSettings {
+            production_percent: 0,
+            instant_resource: None,
+            instant_cost_per_minute: 1,
+        }
+    }
+}
+
+/// When raiders show up and how hard they hit.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RaidSettings {
+    pub first_after_seconds: i32,
+    pub interval_seconds: i32,
+    /// Raiders in the first raid; every later raid brings `strength_growth` more.
+    pub base_strength: i32,
+    pub strength_growth: i32,
+    /// Seconds a raid party needs to move one tile.
+    pub move_seconds: i32,
+    /// Amount of every resource each surviving raider carries off.
+    pub loot_per_raider: i32,
+}
+
+impl Default for RaidSettings {
+    fn default() -> Self {
+        RaidSettings {
+            first_after_seconds: 300,
+            interval_seconds: 240,
+            base_strength: 3,
+            strength_growth: 2,
+            move_seconds: 1,
+            loot_per_raider: 50,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct BuildingDetail {
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    pub fg: String,
+    pub bg: String,
+    pub glyph: char,
+    pub resource_type: Option<ResourceType>,
+    /// Ground the building has to be placed on or next to.
+    pub placement: Option<PlacementRule>,
+    /// Tiles around the footprint the generator harvests deposits from;
+    /// without it the generator produces forever.
+    pub harvest_radius: Option<i32>,
+    pub levels: HashMap<i32, LevelDetail>,
+}
+
+/// Counts `tile` under the footprint and up to `radius` tiles around it.
+/// At least `min_tiles` are needed to build; every tile beyond that adds
+/// `bonus_per_tile` percent to the generator rate, up to `max_bonus`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlacementRule {
+    pub tile: TileType,
+    #[serde(default)]
+    pub radius: i32,
+    #[serde(default = "PlacementRule::default_min_tiles")]
+    pub min_tiles: i32,
+    #[serde(default)]
+    pub bonus_per_tile: i32,
+    #[serde(default)]
+    pub max_bonus: i32,
+}
+
+impl PlacementRule {
+    fn default_min_tiles() -> i32 {
+        1
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LevelDetail {
+    pub rate: Option<i32>,
+    pub requirements: Option<ConstructionRequirment>,
+    /// Extra cap per resource while the building stands at this level.
+    pub storage: Option<HashMap<ResourceType, i32>>,
+    /// Resources a converter consumes per second.
+    pub inputs: Option<HashMap<ResourceType, i32>>,
+    /// Resources a converter produces per second from its inputs.
+    pub outputs: Option<HashMap<ResourceType, i32>>,
+    /// Citizens the building houses.
+    pub housing: Option<i32>,
+    /// Worker slots; a generator only reaches its full rate when all are filled.
+    pub workers: Option<i32>,
+    /// Resources the building consumes per second just by standing.
+    pub upkeep: Option<HashMap<ResourceType, i32>>,
+    /// Soldiers the building trains and keeps as its garrison.
+    pub training: Option<TrainingDetail>,
+    /// Seconds a builder needs to get the building to this level.
+    #[serde(default)]
+    pub build_seconds: i32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TrainingDetail {
+    /// Seconds to train one soldier.
+    pub seconds: i32,
+    /// Paid for every soldier when it finishes training.
+    pub cost: HashMap<ResourceType, i32>,
+    pub max_garrison: i32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ConstructionRequirment {
+    pub current_player_level: Option<i32>,
+    pub current_building_level: Option<i32>,
+    /// Every other key is a resource id and the amount it costs.
+    #[serde(flatten)]
+    pub resources: HashMap<ResourceType, i32>,
+}
+
+/// Reads and validates the manifest. Every problem found is returned so that
+/// content authors can fix them all in one pass.
+pub fn load_manifest(path: &str) -> Result<ConstructionManifest, Vec<String>> {
+    let file = File::open(path).map_err(|e| vec![format!("cannot open {}: {}", path, e)])?;
+    let reader = BufReader::new(file);
+    let manifest = serde_json::from_reader::<_, ConstructionManifest>(reader)
+        .map_err(|e| vec![format!("invalid JSON: {}", e)])?;
+
+    let errors = validate_manifest(&manifest);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(manifest)
+}
+
+pub fn validate_manifest(manifest: &ConstructionManifest) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if manifest.offline.efficiency < 0 {
+        errors.push(format!(
+            "offline: efficiency must not be negative, got {}",
+            manifest.offline.efficiency
+        ));
+    }
+    if let Some(max_seconds) = manifest.offline.max_seconds {
+        if max_seconds < 0 {
+            errors.push(format!(
+                "offline: max_seconds must not be negative, got {}",
+                max_seconds
+            ));
+        }
+    }
+
+    if !(0..=100).contains(&manifest.tear_down_refund) {
+        errors.push(format!(
+            "tear_down_refund must be a percentage between 0 and 100, got {}",
+            manifest.tear_down_refund
+        ));
+    }
+
+    if manifest.builder_slots <= 0 {
+        errors.push(format!(
+            "builder_slots must be positive, got {}",
+            manifest.builder_slots
+        ));
+    }
+
+    let population = &manifest.population;
+    if population.starting < 0 || population.base_housing < 0 {
+        errors.push(format!(
+            "population: starting and base_housing must not be negative, got {} and {}",
+            population.starting, population.base_housing
+        ));
+    }
+    if population.growth_seconds <= 0 {
+        errors.push(format!(
+            "population: growth_seconds must be positive, got {}",
+            population.growth_seconds
+        ));
+    }
+    if population.growth_food < 0 {
+        errors.push(format!(
+            "population: growth_food must not be negative, got {}",
+            population.growth_food
+        ));
+    }
+    for (field, value) in [
+        ("citizens_per_food", population.citizens_per_food),
+        (
+            "starvation_loss_seconds",
+            population.starvation_loss_seconds,
+        ),
+    ] {
+        if value <= 0 {
+            errors.push(format!(
+                "population: {} must be positive, got {}",
+                field, value
+            ));
+        }
+    }
+    if !(0..=100).contains(&population.starvation_penalty) {
+        errors.push(format!(
+            "population: starvation_penalty must be a percentage between 0 and 100, got {}",
+            population.starvation_penalty
+        ));
+    }
+    if population.starvation_grace_seconds < 0 {
+        errors.push(format!(
+            "population: starvation_grace_seconds must not be negative, got {}",
+            population.starvation_grace_seconds
+        ));
+    }
+    if manifest.resource(&population.food).is_none() {
+        errors.push(format!(
+            "population: unknown food resource \"{}\"",
+            population.food
+        ));
+    }
+
+    let upgrades = &manifest.upgrades;
+    if !(0..=100).contains(&upgrades.production_percent) {
+        errors.push(format!(
+            "upgrades: production_percent must be between 0 and 100, got {}",
+            upgrades.production_percent
+        ));
+    }
+    if upgrades.instant_cost_per_minute <= 0 {
+        errors.push(format!(
+            "upgrades: instant_cost_per_minute must be positive, got {}",
+            upgrades.instant_cost_per_minute
+        ));
+    }
+    if let Some(resource) = &upgrades.instant_resource {
+        if manifest.resource(resource).is_none() {
+            errors.push(format!(
+                "upgrades: unknown instant_resource \"{}\"",
+                resource
+            ));
+        }
+    }
+
+    let raids = &manifest.raids;
+    for (field, value) in [
+        ("first_after_seconds", raids.first_after_seconds),
+        ("interval_seconds", raids.interval_seconds),
+        ("move_seconds", raids.move_seconds),
+    ] {
+        if value <= 0 {
+            errors.push(format!("raids: {} must be positive, got {}", field, value));
+        }
+    }
+    for (field, value) in [
+        ("base_strength", raids.base_strength),
+        ("strength_growth", raids.strength_growth),
+        ("loot_per_raider", raids.loot_per_raider),
+    ] {
+        if value < 0 {
+            errors.push(format!(
+                "raids: {} must not be negative, got {}",
+                field, value
+            ));
+        }
+    }
+
+    if manifest.resources.is_empty() {
+        errors.push("no resources declared".to_string());
+    }
+    let mut ids = HashSet::new();
+    for (idx, resource) in manifest.resources.iter().enumerate() {
+        let mut error = |message: String| {
+            errors.push(format!(
+                "resources[{}] \"{}\": {}",
+                idx, resource.id, message
+            ));
+        };
+
+        if !ids.insert(resource.id.as_str()) {
+            error("duplicate resource id".to_string());
+        }
+        if utils::parse_color(&resource.fg).is_none() {
+            error(format!("unknown fg colour \"{}\"", resource.fg));
+        }
+        if resource.base_cap <= 0 {
+            error(format!(
+                "base_cap must be positive, got {}",
+                resource.base_cap
+            ));
+        }
+        if resource.starting_amount < 0 || resource.starting_amount > resource.base_cap {
+            error(format!(
+                "starting_amount must be between 0 and base_cap, got {}",
+                resource.starting_amount
+            ));
+        }
+    }
+
+    let mut deposit_tiles = HashSet::new();
+    for (idx, deposit) in manifest.deposits.iter().enumerate() {
+        let mut error = |message: String| {
+            errors.push(format!("deposits[{}] {:?}: {}", idx, deposit.tile, message));
+        };
+
+        if !deposit_tiles.insert(deposit.tile) {
+            error("duplicate deposit tile".to_string());
+        }
+        if !ids.contains(deposit.resource.as_str()) {
+            error(format!("unknown resource \"{}\"", deposit.resource));
+        }
+        if deposit.amount <= 0 {
+            error(format!("amount must be positive, got {}", deposit.amount));
+        }
+        if deposit.depleted_tile == deposit.tile {
+            error("depleted_tile must differ from tile".to_string());
+        }
+        if deposit.regrow_seconds.is_some_and(|seconds| seconds <= 0) {
+            error(format!(
+                "regrow_seconds must be positive, got {:?}",
+                deposit.regrow_seconds
+            ));
+        }
+    }
+
+    let mut names = HashSet::new();
+    for (idx, detail) in manifest.buildings.iter().enumerate() {
+        let mut error = |message: String| {
+            errors.push(format!(
+                "buildings[{}] \"{}\": {}",
+                idx, detail.name, message
+            ));
+        };
+
+        if !names.insert(detail.name.as_str()) {
+            error("duplicate building name".to_string());
+        }
+
+        // footprint
+        if detail.width <= 0 || detail.height <= 0 {
+            error(format!(
+                "footprint must be positive, got {}x{}",
+                detail.width, detail.height
+            ));
+        } else if detail.width > MAP_WIDTH as i32 || detail.height > MAP_HEIGHT as i32 {
+            error(format!(
+                "footprint {}x{} does not fit on the {}x{} map",
+                detail.width, detail.height, MAP_WIDTH, MAP_HEIGHT
+            ));
+        }
+
+        // placement
+        if let Some(rule) = &detail.placement {
+            if rule.radius < 0 || rule.min_tiles < 0 {
+                error(format!(
+                    "placement radius and min_tiles must not be negative, got {} and {}",
+                    rule.radius, rule.min_tiles
+                ));
+            }
+            if rule.bonus_per_tile < 0 || rule.max_bonus < 0 {
+                error(format!(
+                    "placement bonus_per_tile and max_bonus must not be negative, got {} and {}",
+                    rule.bonus_per_tile, rule.max_bonus
+                ));
+            }
+            if !rule.tile.is_passable() && rule.radius == 0 {
+                error(format!(
+                    "placement needs {:?} under the footprint, which can't be built on",
+                    rule.tile
+                ));
+            }
+        }
+
+        // colours
+        if utils::parse_color(&detail.fg).is_none() {
+            error(format!("unknown fg colour \"{}\"", detail.fg));
+        }
+        if utils::parse_color(&detail.bg).is_none() {
+            error(format!("unknown bg colour \"{}\"", detail.bg));
+        }
+
+        // levels
+        let mut levels: Vec<i32> = detail.levels.keys().copied().collect();
+        levels.sort_unstable();
+        if !detail.levels.contains_key(&0) {
+            error("missing level 0".to_string());
+        }
+        for level in levels.iter().filter(|level| **level < 0) {
+            error(format!("level {} is negative", level));
+        }
+        let missing: Vec<String> = (1..levels.len() as i32)
+            .filter(|level| !detail.levels.contains_key(level))
+            .map(|level| level.to_string())
+            .collect();
+        if !missing.is_empty() {
+            error(format!(
+                "level numbers must be contiguous from 0, missing {} (found {:?})",
+                missing.join(", "),
+                levels
+            ));
+        }
+
+        if let Some(resource_type) = &detail.resource_type {
+            if !ids.contains(resource_type.as_str()) {
+                error(format!("unknown resource_type \"{}\"", resource_type));
+            }
+        }
+
+        if let Some(radius) = detail.harvest_radius {
+            if radius < 0 {
+                error(format!(
+                    "harvest_radius must not be negative, got {}",
+                    radius
+                ));
+            }
+            match &detail.resource_type {
+                None => error("harvest_radius needs a resource_type".to_string()),
+                Some(resource_type) => {
+                    if !manifest
+                        .deposits
+                        .iter()
+                        .any(|deposit| &deposit.resource == resource_type)
+                    {
+                        error(format!("no deposit holds \"{}\" to harvest", resource_type));
+                    }
+                }
+            }
+        }
+
+        let is_converter = detail.levels.get(&0).is_some_and(|l| l.outputs.is_some());
+        for level in levels.iter() {
+            let level_detail = &detail.levels[level];
+            match (&detail.resource_type, level_detail.rate) {
+                (Some(resource_type), None) => error(format!(
+                    "level {} has no rate but the building generates {}",
+                    level, resource_type
+                )),
+                (None, Some(_)) => error(format!(
+                    "level {} has a rate but the building has no resource_type",
+                    level
+                )),
+                (Some(resource_type), Some(rate)) if rate <= 0 => error(format!(
+                    "level {} has a non-positive rate for {}: {}",
+                    level, resource_type, rate
+                )),
+                _ => {}
+            }
+
+            // converters
+            match (&level_detail.inputs, &level_detail.outputs) {
+                (Some(_), None) | (None, Some(_)) => error(format!(
+                    "level {} needs both inputs and outputs to convert resources",
+                    level
+                )),
+                (None, None) if is_converter => {
+                    error(format!("level {} has no inputs and outputs", level))
+                }
+                (Some(_), Some(_)) if !is_converter => error(format!(
+                    "level {} converts resources but level 0 does not",
+                    level
+                )),
+                (Some(_), Some(_)) if level_detail.rate.is_some() => error(format!(
+                    "level {} cannot have both a rate and inputs/outputs",
+                    level
+                )),
+                _ => {}
+            }
+            for (kind, rates) in [
+                ("input", &level_detail.inputs),
+                ("output", &level_detail.outputs),
+                ("upkeep", &level_detail.upkeep),
+            ] {
+                for (resource, rate) in rates.iter().flatten() {
+                    if !ids.contains(resource.as_str()) {
+                        error(format!(
+                            "level {} has an unknown {} \"{}\"",
+                            level, kind, resource
+                        ));
+                    } else if *rate <= 0 {
+                        error(format!(
+                            "level {} has a non-positive {} rate for {}: {}",
+                            level, kind, resource, rate
+                        ));
+                    }
+                }
+            }
+
+            if level_detail.housing.unwrap_or(0) < 0 {
+                error(format!(
+                    "level {} has a negative housing of {}",
+                    level,
+                    level_detail.housing.unwrap_or(0)
+                ));
+            }
+            if let Some(workers) = level_detail.workers {
+                if workers <= 0 {
+                    error(format!(
+                        "level {} must have a positive number of workers, got {}",
+                        level, workers
+                    ));
+                } else if level_detail.rate.is_none() {
+                    error(format!(
+                        "level {} has workers but only generators can be staffed",
+                        level
+                    ));
+                }
+            }
+
+            if level_detail.build_seconds < 0 {
+                error(format!(
+                    "level {} has a negative build_seconds of {}",
+                    level, level_detail.build_seconds
+                ));
+            }
+
+            if let Some(training) = &level_detail.training {
+                if training.seconds <= 0 || training.max_garrison <= 0 {
+                    error(format!(
+                        "level {} must train with positive seconds and max_garrison, got {} and {}",
+                        level, training.seconds, training.max_garrison
+                    ));
+                }
+                for (resource, amount) in training.cost.iter() {
+                    if !ids.contains(resource.as_str()) {
+                        error(format!(
+                            "level {} trains with unknown resource \"{}\"",
+                            level, resource
+                        ));
+                    } else if *amount < 0 {
+                        error(format!(
+                            "level {} has a negative training cost for {}: {}",
+                            level, resource, amount
+                        ));
+                    }
+                }
+            }
+
+            if let Some(storage) = &level_detail.storage {
+                for (resource, capacity) in storage.iter() {
+                    if !ids.contains(resource.as_str()) {
+                        error(format!(
+                            "level {} stores unknown resource \"{}\"",
+                            level, resource
+                        ));
+                    } else if *capacity < 0 {
+                        error(format!(
+                            "level {} has a negative {} storage of {}",
+                            level, resource, capacity
+                        ));
+                    }
+                }
+            }
+
+            if let Some(req) = &level_detail.requirements {
+                for (resource, amount) in req.resources.iter() {
+                    if !ids.contains(resource.as_str()) {
+                        error(format!(
+                            "level {} requires unknown resource \"{}\"",
+                            level, resource
+                        ));
+                    } else if *amount < 0 {
+                        error(format!(
+                            "level {} requires a negative amount of {}: {}",
+                            level, resource, amount
+                        ));
+                    }
+                }
+
+                if let Some(req_building_level) = req.current_building_level {
+                    if *level == 0 {
+                        error("level 0 cannot require a current_building_level".to_string());
+                    } else if req_building_level != level - 1 {
+                        error(format!(
+                            "level {} requires current_building_level {}, expected {}",
+                            level,
+                            req_building_level,
+                            level - 1
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+pub fn report_errors(path: &str, errors: &[String]) {
+    eprintln!("{}: {} error(s)", path, errors.len());
+    for error in errors {
+        eprintln!("  - {}", error);
+    }
+}
+
+/// Entry point of `--check-manifest`; returns the process exit code.
+pub fn check_manifest(path: &str) -> i32 {
+    match load_manifest(path) {
+        Ok(manifest) => {
+            println!("{}: ok ({} buildings)", path, manifest.buildings.len());
+            0
+        }
+        Err(errors) => {
+            report_errors(path, &errors);
+            1
+        }
+    }
+}
+
+/// Watches the manifest file so designers can tweak it while the game runs.
+pub struct ManifestWatcher {
+    pub path: String,
+    pub modified: Option<SystemTime>,
+    pub last_poll: Instant,
+    /// Set when the latest reload was rejected; the previous manifest stays active.
+    pub notice: Option<String>,
+}
+
+impl ManifestWatcher {
+    pub fn new(path: &str) -> Self {
+        ManifestWatcher {
+            path: path.to_string(),
+            modified: modified_time(path),
+            last_poll: Instant::now(),
+            notice: None,
+        }
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Reloads the manifest when its mtime changes and pushes the new numbers
+/// onto the buildings that already exist.
+pub fn poll_manifest(ecs: &mut World) {
+    let path = {
+        let mut watcher = ecs.write_resource::<ManifestWatcher>();
+        if watcher.last_poll.elapsed() < MANIFEST_POLL_INTERVAL {
+            return;
+        }
+        watcher.last_poll = Instant::now();
+
+        let modified = modified_time(&watcher.path);
+        if modified == watcher.modified {
+            return;
+        }
+        watcher.modified = modified;
+        watcher.path.clone()
+    };
+
+    let result = load_manifest(&path).and_then(|manifest| {
+        let errors = check_existing_buildings(ecs, &manifest);
+        if errors.is_empty() {
+            Ok(manifest)
+        } else {
+            Err(errors)
+        }
+    });
+
+    match result {
+        Ok(manifest) => {
+            apply_manifest(ecs, &manifest);
+            ecs.insert(manifest);
+            ecs.write_resource::<ManifestWatcher>().notice = None;
+        }
+        Err(errors) => {
+            report_errors(&path, &errors);
+            let mut notice = format!("Reloading {} failed: {}", path, errors[0]);
+            if errors.len() > 1 {
+                notice += &format!(" (+{} more)", errors.len() - 1);
+            }
+            ecs.write_resource::<ManifestWatcher>().notice = Some(notice);
+        }
+    }
+}
+
+/// A reload must still describe every building that is standing on the map
+/// or under construction.
+fn check_existing_buildings(ecs: &World, manifest: &ConstructionManifest) -> Vec<String> {
+    let buildings = ecs.read_storage::<Building>();
+    let names = ecs.read_storage::<Name>();
+    let sites = ecs.read_storage::<ConstructionSite>();
+    let renovations = ecs.read_storage::<Renovation>();
+
+    let mut errors = Vec::new();
+    for site in sites.join() {
+        if manifest.building(&site.name).is_none() {
+            errors.push(format!(
+                "\"{}\" is still under construction but missing from the manifest",
+                site.name
+            ));
+        }
+    }
+    for (building, name) in (&buildings, &names).join() {
+        let detail = manifest.buildings.iter().find(|b| b.name == name.name);
+        match detail {
+            None => errors.push(format!(
+                "\"{}\" is still on the map but missing from the manifest",
+                name.name
+            )),
+            Some(detail) if !detail.levels.contains_key(&building.level) => errors.push(format!(
+                "\"{}\" at level {} is on the map but the level is missing",
+                name.name, building.level
+            )),
+            _ => {}
+        }
+    }
+    for (renovation, name) in (&renovations, &names).join() {
+        let detail = manifest.buildings.iter().find(|b| b.name == name.name);
+        if detail.is_some_and(|detail| !detail.levels.contains_key(&renovation.level)) {
+            errors.push(format!(
+                "\"{}\" is being upgraded to level {} but the level is missing",
+                name.name, renovation.level
+            ));
+        }
+    }
+    errors.sort();
+    errors.dedup();
+
+    errors
+}
+
+fn apply_manifest(ecs: &mut World, manifest: &ConstructionManifest) {
+    let buildings: Vec<(Entity, String, i32)> = {
+        let entities = ecs.entities();
+        let buildings = ecs.read_storage::<Building>();
+        let names = ecs.read_storage::<Name>();
+        (&entities, &buildings, &names)
+            .join()
+            .map(|(entity, building, name)| (entity, name.name.clone(), building.level))
+            .collect()
+    };
+
+    for (entity, name, level) in buildings {
+        if let Some(detail) = manifest.building(&name) {
+            utils::apply_level(ecs, entity, detail, level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shipped() -> ConstructionManifest {
+        load_manifest(MANIFEST_PATH).expect("The shipped manifest must be valid")
+    }
+
+    fn farm(manifest: &mut ConstructionManifest) -> &mut BuildingDetail {
+        manifest
+            .buildings
+            .iter_mut()
+            .find(|building| building.name == "Farm")
+            .unwrap()
+    }
+
+    fn has_error(manifest: &ConstructionManifest, needle: &str) -> bool {
+        validate_manifest(manifest)
+            .iter()
+            .any(|error| error.contains(needle))
+    }
+
+    #[test]
+    fn shipped_manifest_is_valid() {
+        assert!(validate_manifest(&shipped()).is_empty());
+    }
+
+    #[test]
+    fn non_positive_generator_rates_are_rejected() {
+        for rate in [0, -2] {
+            let mut manifest = shipped();
+            farm(&mut manifest).levels.get_mut(&1).unwrap().rate = Some(rate);
+            assert!(has_error(&manifest, "non-positive rate for food"));
+        }
+    }
+
+    #[test]
+    fn missing_level_zero_and_gaps_are_rejected() {
+        let mut manifest = shipped();
+        let level = farm(&mut manifest).levels.remove(&0).unwrap();
+        farm(&mut manifest).levels.insert(2, level);
+        assert!(has_error(&manifest, "level 0"));
+    }
+
+    #[test]
+    fn bad_footprints_colours_and_duplicates_are_rejected() {
+        let mut manifest = shipped();
+        farm(&mut manifest).width = 0;
+        farm(&mut manifest).fg = "NOT_A_COLOUR".to_string();
+        let copy = manifest.buildings[0].clone();
+        manifest.buildings.push(copy);
+
+        assert!(has_error(&manifest, "footprint must be positive"));
+        assert!(has_error(&manifest, "NOT_A_COLOUR"));
+        assert!(has_error(&manifest, "duplicate building name"));
+    }
+}
@@ -1,32 +1,85 @@
-use rltk::{Algorithm2D, BaseMap, Point, RandomNumberGenerator, Rltk, RGB};
+use rltk::{Algorithm2D, BaseMap, FontCharType, Point, Rltk, RGB};
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use std::cmp::{max, min};
+use std::collections::BTreeMap;
 
-use crate::{WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::{Camera, Rect, WINDOW_HEIGHT, WINDOW_WIDTH};
 
 pub const MAP_PADDING_LEFT: usize = 1;
 pub const MAP_PADDING_RIGHT: usize = 1;
 pub const MAP_PADDING_UP: usize = 1;
 pub const MAP_PADDING_BOTTOM: usize = 15;
-pub const MAP_WIDTH: usize = WINDOW_WIDTH - MAP_PADDING_LEFT - MAP_PADDING_RIGHT;
-pub const MAP_HEIGHT: usize = WINDOW_HEIGHT - MAP_PADDING_UP - MAP_PADDING_BOTTOM;
+/// size of the map window on screen
+pub const VIEW_WIDTH: usize = WINDOW_WIDTH - MAP_PADDING_LEFT - MAP_PADDING_RIGHT;
+pub const VIEW_HEIGHT: usize = WINDOW_HEIGHT - MAP_PADDING_UP - MAP_PADDING_BOTTOM;
+/// size of the world, which the camera scrolls over
+pub const MAP_WIDTH: usize = 500;
+pub const MAP_HEIGHT: usize = 500;
 pub const MAP_COUNT: usize = MAP_HEIGHT * MAP_WIDTH;
 
-#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum TileType {
-    Wall,
+    Wall, // cliffs
     Floor,
+    Forest,
+    Rock,
+    Water,
+    Fertile,
+    Stumps, // a felled forest
 }
 
+impl TileType {
+    /// Whether anything can stand, or be built, on the tile.
+    pub fn is_passable(&self) -> bool {
+        !matches!(self, TileType::Wall | TileType::Water)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TileType::Wall => "cliff",
+            TileType::Floor => "open ground",
+            TileType::Forest => "forest",
+            TileType::Rock => "rock",
+            TileType::Water => "water",
+            TileType::Fertile => "fertile soil",
+            TileType::Stumps => "stumps",
+        }
+    }
+}
+
+/// A tile that turns back into `tile` at `at`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Regrowth {
+    pub idx: usize,
+    pub tile: TileType,
+    pub at: i64, // second
+}
+
+/// Saves keep only the seed and what changed since the map was generated;
+/// `map_builder::rebuild_terrain` brings the tiles back on load.
 #[derive(Default, Serialize, Deserialize, Clone)]
 pub struct Map {
+    #[serde(skip)]
     pub tiles: Vec<TileType>,
     pub width: i32,
     pub height: i32,
+    /// the map generator seed; 0 for a blank map
+    #[serde(default)]
+    pub seed: u64,
+    /// tiles that differ from the generated terrain, by index
+    #[serde(default)]
+    pub edits: BTreeMap<usize, TileType>,
     /// the coordinates of the left-top point of buildings on the map are
     // marked as true
+    #[serde(with = "sparse_flags")]
     pub occupied: Vec<bool>,
+    /// amount already harvested from the deposit on each tile
+    #[serde(with = "sparse_amounts")]
+    pub drawn: Vec<i32>,
+    /// exhausted deposits waiting to grow back
+    #[serde(default)]
+    pub regrowing: Vec<Regrowth>,
 
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
@@ -39,30 +92,64 @@ impl Map {
             tiles: vec![TileType::Floor; MAP_COUNT],
             width: MAP_WIDTH as i32,
             height: MAP_HEIGHT as i32,
+            seed: 0,
+            edits: BTreeMap::new(),
             occupied: vec![false; MAP_COUNT],
+            drawn: vec![0; MAP_COUNT],
+            regrowing: Vec::new(),
             tile_content: vec![Vec::new(); MAP_COUNT],
         }
     }
 
-    pub fn xy_idx(&self, mut x: i32, mut y: i32) -> usize {
-        y -= MAP_PADDING_UP as i32;
-        x -= MAP_PADDING_LEFT as i32;
+    /// Changes a tile after generation, remembering it for the save.
+    pub fn set_tile(&mut self, idx: usize, tile: TileType) {
+        self.tiles[idx] = tile;
+        self.edits.insert(idx, tile);
+    }
 
-        if x < 0 || y < 0 {
+    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
             panic!("Coordinates out of bound");
         }
 
         (y as usize * self.width as usize) + x as usize
     }
 
+    /// Whether every tile under `rect` can be built on.
+    pub fn is_buildable(&self, rect: &Rect) -> bool {
+        (rect.x1..rect.x2)
+            .all(|x| (rect.y1..rect.y2).all(|y| self.tiles[self.xy_idx(x, y)].is_passable()))
+    }
+
+    /// Tiles of `tile` under `rect` and up to `radius` tiles around it.
+    pub fn count_tiles(&self, rect: &Rect, radius: i32, tile: TileType) -> i32 {
+        let x1 = max(rect.x1 - radius, 0);
+        let x2 = min(rect.x2 + radius, self.width);
+        let y1 = max(rect.y1 - radius, 0);
+        let y2 = min(rect.y2 + radius, self.height);
+
+        let mut count = 0;
+        for x in x1..x2 {
+            for y in y1..y2 {
+                if self.tiles[self.xy_idx(x, y)] == tile {
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
     pub fn idx_xy(&self, idx: usize) -> (i32, i32) {
-        let x = idx % MAP_WIDTH;
-        let y = idx / MAP_WIDTH;
+        let x = idx % self.width as usize;
+        let y = idx / self.width as usize;
+
+        (x as i32, y as i32)
+    }
 
-        (
-            x as i32 + MAP_PADDING_LEFT as i32,
-            y as i32 + MAP_PADDING_UP as i32,
-        )
+    /// Whether `rect` lies entirely on the map.
+    pub fn contains(&self, rect: &Rect) -> bool {
+        rect.x1 >= 0 && rect.y1 >= 0 && rect.x2 <= self.width && rect.y2 <= self.height
     }
 
     // fn is_exit_valid(&self, x: i32, y: i32) -> bool {
@@ -94,7 +181,7 @@ impl Algorithm2D for Map {
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx as usize] == TileType::Wall
+        matches!(self.tiles[idx], TileType::Wall | TileType::Forest)
     }
 
     // fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
@@ -146,38 +233,91 @@ pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
     ctx.draw_box(
         0,
         0,
-        MAP_WIDTH + MAP_PADDING_LEFT,
-        MAP_HEIGHT + MAP_PADDING_UP,
+        VIEW_WIDTH + MAP_PADDING_LEFT,
+        VIEW_HEIGHT + MAP_PADDING_UP,
         RGB::named(rltk::WHITE),
         RGB::named(rltk::BLACK),
     );
 
     let map = ecs.fetch::<Map>();
+    let camera = ecs.fetch::<Camera>();
 
-    let mut y = MAP_PADDING_UP;
-    let mut x = MAP_PADDING_LEFT;
-    for (idx, tile) in map.tiles.iter().enumerate() {
-        // Render a tile depending upon the tile type
-
-        let glyph;
-        let fg;
-        match tile {
-            TileType::Floor => {
-                glyph = rltk::to_cp437(' ');
-                fg = RGB::from_f32(0.0, 0.5, 0.5);
+    for screen_y in 0..VIEW_HEIGHT as i32 {
+        for screen_x in 0..VIEW_WIDTH as i32 {
+            let x = camera.x + screen_x;
+            let y = camera.y + screen_y;
+            if x >= map.width || y >= map.height {
+                continue;
             }
-            TileType::Wall => {
-                glyph = rltk::to_cp437('#');
-                fg = RGB::from_f32(0., 1.0, 0.);
+
+            let (glyph, fg) = tile_glyph(map.tiles[map.xy_idx(x, y)]);
+            ctx.set(
+                screen_x + MAP_PADDING_LEFT as i32,
+                screen_y + MAP_PADDING_UP as i32,
+                fg,
+                RGB::from_f32(0., 0., 0.),
+                glyph,
+            );
+        }
+    }
+}
+
+/// How a tile type is drawn.
+pub fn tile_glyph(tile: TileType) -> (FontCharType, RGB) {
+    match tile {
+        TileType::Floor => (rltk::to_cp437(' '), RGB::from_f32(0.0, 0.5, 0.5)),
+        TileType::Wall => (rltk::to_cp437('▲'), RGB::named(rltk::SADDLEBROWN)),
+        TileType::Forest => (rltk::to_cp437('♣'), RGB::named(rltk::FORESTGREEN)),
+        TileType::Rock => (rltk::to_cp437('∩'), RGB::named(rltk::GRAY50)),
+        TileType::Water => (rltk::to_cp437('≈'), RGB::named(rltk::STEELBLUE)),
+        TileType::Fertile => (rltk::to_cp437('"'), RGB::named(rltk::DARKOLIVEGREEN)),
+        TileType::Stumps => (rltk::to_cp437(','), RGB::named(rltk::SIENNA)),
+    }
+}
+
+/// Saves a per-tile flag as the indices where it is set.
+mod sparse_flags {
+    use super::MAP_COUNT;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(flags: &[bool], serializer: S) -> Result<S::Ok, S::Error> {
+        let set: Vec<usize> = (0..flags.len()).filter(|idx| flags[*idx]).collect();
+        set.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<bool>, D::Error> {
+        let mut flags = vec![false; MAP_COUNT];
+        for idx in Vec::<usize>::deserialize(deserializer)? {
+            if let Some(flag) = flags.get_mut(idx) {
+                *flag = true;
             }
         }
-        ctx.set(x, y, fg, RGB::from_f32(0., 0., 0.), glyph);
+        Ok(flags)
+    }
+}
 
-        // Move the coordinates
-        x += 1;
-        if x >= MAP_PADDING_LEFT + MAP_WIDTH {
-            x = MAP_PADDING_LEFT;
-            y += 1;
+/// Saves a per-tile amount as the tiles where it isn't zero.
+mod sparse_amounts {
+    use super::MAP_COUNT;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(amounts: &[i32], serializer: S) -> Result<S::Ok, S::Error> {
+        let set: Vec<(usize, i32)> = amounts
+            .iter()
+            .enumerate()
+            .filter(|(_, amount)| **amount != 0)
+            .map(|(idx, amount)| (idx, *amount))
+            .collect();
+        set.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i32>, D::Error> {
+        let mut amounts = vec![0; MAP_COUNT];
+        for (idx, amount) in Vec::<(usize, i32)>::deserialize(deserializer)? {
+            if let Some(slot) = amounts.get_mut(idx) {
+                *slot = amount;
+            }
         }
+        Ok(amounts)
     }
 }
@@ -0,0 +1,242 @@
+use rltk::{FastNoise, FractalType, NoiseType, RandomNumberGenerator};
+use std::cmp::{max, min};
+
+use super::{Map, TileType, MAP_COUNT, MAP_HEIGHT, MAP_WIDTH};
+
+const CLIFF_FILL_PERCENT: i32 = 38;
+const CLIFF_ITERATIONS: usize = 6;
+const LAKE_THRESHOLD: f32 = -0.45;
+const FOREST_THRESHOLD: f32 = 0.25;
+const FERTILE_RADIUS: i32 = 4;
+const FERTILE_THRESHOLD: f32 = 0.35;
+const ROCK_OUTCROP_AREA: usize = 500; // map tiles per outcrop
+const ROCK_OUTCROP_SIZE: i32 = 30;
+
+/// Builds the terrain for a new game. The same seed always yields the same map.
+pub fn build_map(seed: u64) -> Map {
+    let mut map = Map::new();
+    map.seed = seed;
+
+    let mut rng = RandomNumberGenerator::seeded(seed);
+    cliffs(&mut map, &mut rng);
+    lakes(&mut map, rng.next_u64());
+    forests(&mut map, rng.next_u64());
+    fertile_soil(&mut map, rng.next_u64());
+    rock_outcrops(&mut map, &mut rng);
+
+    map
+}
+
+/// Puts back the tiles of a loaded map, which saves leave out: the terrain
+/// its seed generates, with the recorded edits on top.
+pub fn rebuild_terrain(map: &mut Map) {
+    map.tiles = if map.seed == 0 {
+        vec![TileType::Floor; MAP_COUNT]
+    } else {
+        build_map(map.seed).tiles
+    };
+    for (idx, tile) in map.edits.iter() {
+        if let Some(slot) = map.tiles.get_mut(*idx) {
+            *slot = *tile;
+        }
+    }
+}
+
+/// Cellular automata: random noise smoothed into a few solid ridges.
+fn cliffs(map: &mut Map, rng: &mut RandomNumberGenerator) {
+    for tile in map.tiles.iter_mut() {
+        if rng.roll_dice(1, 100) <= CLIFF_FILL_PERCENT {
+            *tile = TileType::Wall;
+        }
+    }
+
+    let width = MAP_WIDTH as i32;
+    let height = MAP_HEIGHT as i32;
+    for _ in 0..CLIFF_ITERATIONS {
+        let mut next = map.tiles.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let mut neighbors = 0;
+                for (dx, dy) in NEIGHBORS {
+                    let (nx, ny) = (x + dx, y + dy);
+                    // out of bounds counts as open ground so cliffs stay off the edges
+                    if nx >= 0
+                        && nx < width
+                        && ny >= 0
+                        && ny < height
+                        && map.tiles[(ny * width + nx) as usize] == TileType::Wall
+                    {
+                        neighbors += 1;
+                    }
+                }
+
+                // walls survive with four walled neighbours, open ground needs five
+                let idx = (y * width + x) as usize;
+                let threshold = if map.tiles[idx] == TileType::Wall {
+                    4
+                } else {
+                    5
+                };
+                next[idx] = if neighbors >= threshold {
+                    TileType::Wall
+                } else {
+                    TileType::Floor
+                };
+            }
+        }
+        map.tiles = next;
+    }
+}
+
+/// Low-frequency noise dips become lakes.
+fn lakes(map: &mut Map, seed: u64) {
+    let mut noise = FastNoise::seeded(seed);
+    noise.set_noise_type(NoiseType::SimplexFractal);
+    noise.set_fractal_type(FractalType::FBM);
+    noise.set_fractal_octaves(3);
+    noise.set_frequency(0.03);
+
+    paint_noise(map, &noise, TileType::Water, |value| value < LAKE_THRESHOLD);
+}
+
+/// Higher-frequency noise peaks become forests.
+fn forests(map: &mut Map, seed: u64) {
+    let mut noise = FastNoise::seeded(seed);
+    noise.set_noise_type(NoiseType::SimplexFractal);
+    noise.set_fractal_type(FractalType::FBM);
+    noise.set_fractal_octaves(4);
+    noise.set_frequency(0.06);
+
+    paint_noise(map, &noise, TileType::Forest, |value| {
+        value > FOREST_THRESHOLD
+    });
+}
+
+/// Open ground near lakes, plus a few meadows, is fertile.
+fn fertile_soil(map: &mut Map, seed: u64) {
+    let width = MAP_WIDTH as i32;
+    let height = MAP_HEIGHT as i32;
+    let water: Vec<usize> = (0..MAP_COUNT)
+        .filter(|idx| map.tiles[*idx] == TileType::Water)
+        .collect();
+    for idx in water {
+        let (x, y) = ((idx % MAP_WIDTH) as i32, (idx / MAP_WIDTH) as i32);
+        for ny in max(y - FERTILE_RADIUS, 0)..min(y + FERTILE_RADIUS + 1, height) {
+            for nx in max(x - FERTILE_RADIUS, 0)..min(x + FERTILE_RADIUS + 1, width) {
+                let nidx = (ny * width + nx) as usize;
+                if map.tiles[nidx] == TileType::Floor {
+                    map.tiles[nidx] = TileType::Fertile;
+                }
+            }
+        }
+    }
+
+    let mut noise = FastNoise::seeded(seed);
+    noise.set_noise_type(NoiseType::SimplexFractal);
+    noise.set_fractal_type(FractalType::FBM);
+    noise.set_fractal_octaves(2);
+    noise.set_frequency(0.05);
+
+    paint_noise(map, &noise, TileType::Fertile, |value| {
+        value > FERTILE_THRESHOLD
+    });
+}
+
+/// Turns open ground where `pick` accepts the noise value into `tile`.
+fn paint_noise(map: &mut Map, noise: &FastNoise, tile: TileType, pick: impl Fn(f32) -> bool) {
+    for idx in 0..MAP_COUNT {
+        if map.tiles[idx] != TileType::Floor {
+            continue;
+        }
+
+        let x = (idx % MAP_WIDTH) as f32;
+        let y = (idx / MAP_WIDTH) as f32;
+        if pick(noise.get_noise(x, y)) {
+            map.tiles[idx] = tile;
+        }
+    }
+}
+
+/// Short drunkard's walks leave small patches of rock on open ground.
+fn rock_outcrops(map: &mut Map, rng: &mut RandomNumberGenerator) {
+    let width = MAP_WIDTH as i32;
+    let height = MAP_HEIGHT as i32;
+
+    for _ in 0..MAP_COUNT / ROCK_OUTCROP_AREA {
+        let mut x = rng.range(0, width);
+        let mut y = rng.range(0, height);
+        for _ in 0..ROCK_OUTCROP_SIZE {
+            let idx = (y * width + x) as usize;
+            if matches!(map.tiles[idx], TileType::Floor | TileType::Fertile) {
+                map.tiles[idx] = TileType::Rock;
+            }
+
+            let (dx, dy) = NEIGHBORS[rng.range(0, NEIGHBORS.len())];
+            x = (x + dx).clamp(0, width - 1);
+            y = (y + dy).clamp(0, height - 1);
+        }
+    }
+}
+
+const NEIGHBORS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_builds_the_same_map() {
+        let map = build_map(7);
+        assert!(build_map(7).tiles == map.tiles);
+        assert!(build_map(8).tiles != map.tiles);
+        assert_eq!(map.seed, 7);
+        assert!(map.edits.is_empty());
+    }
+
+    #[test]
+    fn generated_map_has_every_kind_of_terrain() {
+        let map = build_map(42);
+        for tile in [
+            TileType::Wall,
+            TileType::Floor,
+            TileType::Forest,
+            TileType::Rock,
+            TileType::Water,
+            TileType::Fertile,
+        ] {
+            assert!(map.tiles.contains(&tile), "no {:?} on the map", tile);
+        }
+        assert!(!map.tiles.contains(&TileType::Stumps));
+    }
+
+    #[test]
+    fn saved_map_rebuilds_to_the_same_terrain() {
+        let mut map = build_map(42);
+        let forest = map
+            .tiles
+            .iter()
+            .position(|tile| *tile == TileType::Forest)
+            .unwrap();
+        map.set_tile(forest, TileType::Stumps);
+        map.occupied[123] = true;
+        map.drawn[456] = 7;
+
+        let saved = serde_json::to_string(&map).unwrap();
+        assert!(saved.len() < 1000);
+
+        let mut loaded: Map = serde_json::from_str(&saved).unwrap();
+        rebuild_terrain(&mut loaded);
+        assert!(loaded.tiles == map.tiles);
+        assert!(loaded.occupied == map.occupied);
+        assert!(loaded.drawn == map.drawn);
+    }
+}
@@ -0,0 +1,540 @@
+use rltk::{RandomNumberGenerator, RGB};
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use std::cmp::{max, min};
+
+use super::components::*;
+use crate::gamelog::{self, GameLog, LogCategory};
+use crate::{resource_system, utils, ConstructionManifest, GameClock, MAP_HEIGHT, MAP_WIDTH};
+
+/// Trains soldiers in every building with a garrison, paying for each one as
+/// it finishes.
+pub struct TrainingSystem {}
+
+impl<'a> System<'a> for TrainingSystem {
+    type SystemData = (
+        WriteStorage<'a, Garrison>,
+        WriteStorage<'a, PlayerStats>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, GameClock>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut garrisons, mut stats, player, clock) = data;
+
+        let player_stats = stats.get_mut(*player).expect("Player must have stats");
+        let current = clock.now();
+
+        for garrison in (&mut garrisons).join() {
+            while garrison.next_soldier <= current {
+                let affordable = garrison
+                    .cost
+                    .iter()
+                    .all(|(resource_type, amount)| player_stats.amount(resource_type) >= *amount);
+                if garrison.soldiers >= garrison.capacity || !affordable {
+                    // try again once a full training period has passed
+                    garrison.next_soldier = current + garrison.seconds as i64;
+                    break;
+                }
+
+                for (resource_type, amount) in garrison.cost.iter() {
+                    if let Some(info) = player_stats.resources.get_mut(resource_type) {
+                        info.amount -= amount;
+                    }
+                }
+                garrison.soldiers += 1;
+                garrison.next_soldier += garrison.seconds as i64;
+            }
+        }
+    }
+}
+
+/// Sends raid parties in from the map edge and marches them towards the
+/// nearest building.
+pub struct RaidSystem {}
+
+impl<'a> System<'a> for RaidSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, GameClock>,
+        ReadExpect<'a, ConstructionManifest>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, GameLog>,
+        Read<'a, LazyUpdate>,
+        WriteStorage<'a, RaidSchedule>,
+        WriteStorage<'a, Raider>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, WantsToRaid>,
+        ReadStorage<'a, Building>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player,
+            clock,
+            manifest,
+            mut rng,
+            mut log,
+            lazy,
+            mut schedules,
+            mut raiders,
+            mut positions,
+            mut wants_raid,
+            buildings,
+        ) = data;
+
+        let settings = &manifest.raids;
+        let current = clock.now();
+
+        // older saves have no schedule yet
+        if schedules.get(*player).is_none() {
+            schedules
+                .insert(
+                    *player,
+                    RaidSchedule {
+                        next_raid: current + settings.first_after_seconds as i64,
+                        raids: 0,
+                    },
+                )
+                .expect("Unable to insert the raid schedule");
+        }
+
+        let schedule = schedules.get_mut(*player).unwrap();
+        if current >= schedule.next_raid {
+            schedule.next_raid = current + settings.interval_seconds as i64;
+
+            // nothing to raid yet
+            if buildings.join().next().is_some() {
+                let strength = settings.base_strength + settings.strength_growth * schedule.raids;
+                schedule.raids += 1;
+
+                let (x, y, side) = edge_position(&mut rng);
+                lazy.create_entity(&entities)
+                    .with(Position { x, y })
+                    .with(Raider {
+                        strength,
+                        next_move: current + settings.move_seconds as i64,
+                    })
+                    .with(Renderable {
+                        glyph: rltk::to_cp437('☻'),
+                        fg: RGB::named(rltk::ORANGE_RED),
+                        bg: RGB::named(rltk::BLACK),
+                        render_order: 1,
+                    })
+                    .with(Name {
+                        name: "Raiders".to_string(),
+                    })
+                    .marked::<SimpleMarker<SerializeMe>>()
+                    .build();
+                log.add(
+                    current,
+                    LogCategory::Military,
+                    format!("{} raiders are approaching from the {}!", strength, side),
+                );
+            }
+        }
+
+        for (entity, raider, pos) in (&entities, &mut raiders, &mut positions).join() {
+            if wants_raid.get(entity).is_some() {
+                continue;
+            }
+
+            while raider.next_move <= current {
+                raider.next_move += settings.move_seconds as i64;
+
+                let target = (&entities, &buildings)
+                    .join()
+                    .min_by_key(|(_, building)| distance_to(building, pos));
+                match target {
+                    None => {
+                        log.add(
+                            current,
+                            LogCategory::Military,
+                            "The raiders found nothing to plunder and left.",
+                        );
+                        entities.delete(entity).expect("Unable to delete raiders");
+                        break;
+                    }
+                    Some((target, building)) => {
+                        if distance_to(building, pos) <= 1 {
+                            wants_raid
+                                .insert(entity, WantsToRaid { target })
+                                .expect("Unable to insert raid");
+                            break;
+                        }
+
+                        let rect = &building.rect;
+                        pos.x += (pos.x.clamp(rect.x1, rect.x2 - 1) - pos.x).signum();
+                        pos.y += (pos.y.clamp(rect.y1, rect.y2 - 1) - pos.y).signum();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A random tile on the map border, and which side it is on.
+fn edge_position(rng: &mut RandomNumberGenerator) -> (i32, i32, &'static str) {
+    let width = MAP_WIDTH as i32;
+    let height = MAP_HEIGHT as i32;
+
+    match rng.roll_dice(1, 4) {
+        1 => (rng.range(0, width), 0, "north"),
+        2 => (rng.range(0, width), height - 1, "south"),
+        3 => (0, rng.range(0, height), "west"),
+        _ => (width - 1, rng.range(0, height), "east"),
+    }
+}
+
+/// Chebyshev distance from a tile to the closest tile of a building.
+fn distance_to(building: &Building, pos: &Position) -> i32 {
+    let rect = &building.rect;
+    let dx = max(max(rect.x1 - pos.x, pos.x - (rect.x2 - 1)), 0);
+    let dy = max(max(rect.y1 - pos.y, pos.y - (rect.y2 - 1)), 0);
+
+    max(dx, dy)
+}
+
+/// Fights raid parties that reached a building against every garrison. Each
+/// soldier takes one raider down with them; survivors loot and damage the
+/// building they reached.
+pub struct CombatSystem {}
+
+impl<'a> System<'a> for CombatSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, GameClock>,
+        ReadExpect<'a, ConstructionManifest>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, Raider>,
+        ReadStorage<'a, WantsToRaid>,
+        WriteStorage<'a, Garrison>,
+        WriteStorage<'a, PlayerStats>,
+        WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, Name>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player,
+            clock,
+            manifest,
+            mut log,
+            raiders,
+            wants_raid,
+            mut garrisons,
+            mut stats,
+            mut suffer_damage,
+            names,
+        ) = data;
+
+        let player_stats = stats.get_mut(*player).expect("Player must have stats");
+        let current = clock.now();
+
+        for (entity, raider, wants) in (&entities, &raiders, &wants_raid).join() {
+            let mut casualties = 0;
+            for garrison in (&mut garrisons).join() {
+                let fallen = min(garrison.soldiers, raider.strength - casualties);
+                garrison.soldiers -= fallen;
+                casualties += fallen;
+            }
+            let survivors = raider.strength - casualties;
+
+            if survivors == 0 {
+                log.add(
+                    current,
+                    LogCategory::Military,
+                    format!(
+                        "Raid of {} repelled, {} soldiers fell.",
+                        raider.strength, casualties
+                    ),
+                );
+            } else {
+                let mut stolen = Vec::new();
+                for resource in manifest.resources.iter() {
+                    if let Some(info) = player_stats.resources.get_mut(&resource.id) {
+                        let amount = min(info.amount, survivors * manifest.raids.loot_per_raider);
+                        if amount > 0 {
+                            info.amount -= amount;
+                            stolen.push(format!("{} {}", amount, resource.name));
+                        }
+                    }
+                }
+
+                let target = names
+                    .get(wants.target)
+                    .map_or("a building".to_string(), |name| name.name.clone());
+                // several parties may hit the same building in one frame
+                match suffer_damage.get_mut(wants.target) {
+                    Some(damage) => damage.amount += 1,
+                    None => {
+                        suffer_damage
+                            .insert(wants.target, SufferDamage { amount: 1 })
+                            .expect("Unable to insert damage");
+                    }
+                }
+
+                let loot = if stolen.is_empty() {
+                    "nothing".to_string()
+                } else {
+                    stolen.join(", ")
+                };
+                log.add(
+                    current,
+                    LogCategory::Military,
+                    format!(
+                        "{} raiders broke through, stole {} and damaged the {}.",
+                        survivors, loot, target
+                    ),
+                );
+            }
+
+            entities.delete(entity).expect("Unable to delete raiders");
+        }
+    }
+}
+
+/// Knocks damaged buildings down a level each, destroying those that were
+/// already at level 0.
+pub fn apply_damage(ecs: &mut World) {
+    let damaged: Vec<(Entity, i32)> = {
+        let entities = ecs.entities();
+        let suffer_damage = ecs.read_storage::<SufferDamage>();
+        (&entities, &suffer_damage)
+            .join()
+            .map(|(entity, damage)| (entity, damage.amount))
+            .collect()
+    };
+    if damaged.is_empty() {
+        return;
+    }
+
+    for (entity, amount) in damaged {
+        utils::cancel_upgrade(ecs, entity, "it was damaged in a raid");
+        let (name, level) = {
+            let buildings = ecs.read_storage::<Building>();
+            let names = ecs.read_storage::<Name>();
+            match (buildings.get(entity), names.get(entity)) {
+                (Some(building), Some(name)) => (name.name.clone(), building.level - amount),
+                _ => continue,
+            }
+        };
+
+        if level < 0 {
+            utils::remove_building(ecs, entity);
+            gamelog::log(
+                ecs,
+                LogCategory::Military,
+                format!("The {} was destroyed.", name),
+            );
+        } else {
+            let manifest = ecs.fetch::<ConstructionManifest>();
+            if let Some(detail) = manifest.building(&name) {
+                utils::apply_level(ecs, entity, detail, level);
+            }
+        }
+    }
+
+    ecs.write_storage::<SufferDamage>().clear();
+    resource_system::refresh_rates(ecs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{advance, now, test_world};
+    use std::collections::HashMap;
+
+    fn garrison(ecs: &mut World, capacity: i32) -> Entity {
+        let next_soldier = now(ecs) + 10;
+        ecs.create_entity()
+            .with(Garrison {
+                soldiers: 0,
+                capacity,
+                seconds: 10,
+                cost: HashMap::from([("food".to_string(), 20)]),
+                next_soldier,
+            })
+            .build()
+    }
+
+    fn food(ecs: &World) -> i32 {
+        let player = *ecs.fetch::<Entity>();
+        ecs.read_storage::<PlayerStats>()
+            .get(player)
+            .unwrap()
+            .amount("food")
+    }
+
+    #[test]
+    fn garrison_trains_one_soldier_per_period() {
+        let mut ecs = test_world();
+        let army = garrison(&mut ecs, 10);
+        let food_before = food(&ecs);
+        let start = now(&ecs);
+
+        advance(&mut ecs, 9);
+        TrainingSystem {}.run_now(&ecs);
+        assert_eq!(
+            ecs.read_storage::<Garrison>().get(army).unwrap().soldiers,
+            0
+        );
+
+        advance(&mut ecs, 26);
+        TrainingSystem {}.run_now(&ecs);
+        let garrisons = ecs.read_storage::<Garrison>();
+        let garrison = garrisons.get(army).unwrap();
+        assert_eq!(garrison.soldiers, 3);
+        assert_eq!(garrison.next_soldier, start + 40);
+        assert_eq!(food(&ecs), food_before - 60);
+    }
+
+    #[test]
+    fn full_garrison_waits_a_period_before_trying_again() {
+        let mut ecs = test_world();
+        let army = garrison(&mut ecs, 1);
+
+        advance(&mut ecs, 100);
+        TrainingSystem {}.run_now(&ecs);
+        let garrisons = ecs.read_storage::<Garrison>();
+        let garrison = garrisons.get(army).unwrap();
+        assert_eq!(garrison.soldiers, 1);
+        assert_eq!(garrison.next_soldier, now(&ecs) + 10);
+    }
+
+    #[test]
+    fn damage_cancels_an_upgrade_with_a_refund() {
+        let mut ecs = test_world();
+        let detail = ecs
+            .fetch::<ConstructionManifest>()
+            .building("Farm")
+            .unwrap()
+            .clone();
+        let building = crate::spawner::spawn_building(&mut ecs, &detail, 10, 10);
+        utils::apply_level(&ecs, building, &detail, 1);
+        let finish_at = now(&ecs) + 60;
+        ecs.write_storage::<Renovation>()
+            .insert(
+                building,
+                Renovation {
+                    level: 2,
+                    seconds: 60,
+                    finish_at,
+                    cost: HashMap::from([("food".to_string(), 100)]),
+                },
+            )
+            .unwrap();
+        let food_before = food(&ecs);
+
+        ecs.write_storage::<SufferDamage>()
+            .insert(building, SufferDamage { amount: 1 })
+            .unwrap();
+        apply_damage(&mut ecs);
+        assert!(!ecs.read_storage::<Renovation>().contains(building));
+        assert_eq!(
+            ecs.read_storage::<Building>().get(building).unwrap().level,
+            0
+        );
+        assert_eq!(food(&ecs), food_before + 100);
+    }
+
+    fn raid(ecs: &mut World, target: Entity, strength: i32) -> Entity {
+        ecs.create_entity()
+            .with(Raider {
+                strength,
+                next_move: 0,
+            })
+            .with(WantsToRaid { target })
+            .build()
+    }
+
+    fn farm(ecs: &mut World, level: i32) -> Entity {
+        let detail = ecs
+            .fetch::<ConstructionManifest>()
+            .building("Farm")
+            .unwrap()
+            .clone();
+        let building = crate::spawner::spawn_building(ecs, &detail, 10, 10);
+        utils::apply_level(ecs, building, &detail, level);
+        building
+    }
+
+    #[test]
+    fn garrison_repels_a_raid_it_outnumbers() {
+        let mut ecs = test_world();
+        let building = farm(&mut ecs, 1);
+        let army = garrison(&mut ecs, 10);
+        ecs.write_storage::<Garrison>()
+            .get_mut(army)
+            .unwrap()
+            .soldiers = 5;
+        let party = raid(&mut ecs, building, 3);
+        let food_before = food(&ecs);
+
+        CombatSystem {}.run_now(&ecs);
+        ecs.maintain();
+        apply_damage(&mut ecs);
+        assert!(!ecs.is_alive(party));
+        assert_eq!(
+            ecs.read_storage::<Garrison>().get(army).unwrap().soldiers,
+            2
+        );
+        assert_eq!(food(&ecs), food_before);
+        assert_eq!(
+            ecs.read_storage::<Building>().get(building).unwrap().level,
+            1
+        );
+    }
+
+    #[test]
+    fn raiders_that_break_through_loot_and_damage_the_building() {
+        let mut ecs = test_world();
+        let building = farm(&mut ecs, 1);
+        let army = garrison(&mut ecs, 10);
+        ecs.write_storage::<Garrison>()
+            .get_mut(army)
+            .unwrap()
+            .soldiers = 1;
+        raid(&mut ecs, building, 3);
+        let food_before = food(&ecs);
+
+        CombatSystem {}.run_now(&ecs);
+        ecs.maintain();
+        apply_damage(&mut ecs);
+        assert_eq!(
+            ecs.read_storage::<Garrison>().get(army).unwrap().soldiers,
+            0
+        );
+        // two survivors at 50 each
+        assert_eq!(food(&ecs), food_before - 100);
+        assert_eq!(
+            ecs.read_storage::<Building>().get(building).unwrap().level,
+            0
+        );
+    }
+
+    #[test]
+    fn two_parties_at_one_building_take_two_levels() {
+        let mut ecs = test_world();
+        let building = farm(&mut ecs, 1);
+        raid(&mut ecs, building, 1);
+        raid(&mut ecs, building, 1);
+
+        CombatSystem {}.run_now(&ecs);
+        ecs.maintain();
+        assert_eq!(
+            ecs.read_storage::<SufferDamage>()
+                .get(building)
+                .unwrap()
+                .amount,
+            2
+        );
+        apply_damage(&mut ecs);
+        ecs.maintain();
+        assert!(!ecs.is_alive(building));
+    }
+}
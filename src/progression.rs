@@ -0,0 +1,115 @@
+use serde::Deserialize;
+use specs::prelude::*;
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::gamelog::{self, LogCategory};
+use crate::PlayerStats;
+
+pub const LEVEL_TABLE_PATH: &str = "src/levels.json";
+
+/// Player level thresholds and XP rewards.
+#[derive(Deserialize, Clone, Debug)]
+pub struct LevelTable {
+    pub construction_xp: i32,
+    pub upgrade_xp: i32,
+    /// Resources that have to be produced to earn one XP.
+    pub resources_per_xp: i32,
+    /// `thresholds[n]` is the total XP needed to reach level n + 1.
+    pub thresholds: Vec<i32>,
+}
+
+impl LevelTable {
+    pub fn level_for(&self, xp: i32) -> i32 {
+        self.thresholds.iter().filter(|t| **t <= xp).count() as i32
+    }
+
+    /// Total XP needed for the level after `level`, if there is one.
+    pub fn next_threshold(&self, level: i32) -> Option<i32> {
+        self.thresholds.get(level as usize).copied()
+    }
+}
+
+pub fn load_level_table(path: &str) -> Result<LevelTable, Vec<String>> {
+    let file = File::open(path).map_err(|e| vec![format!("cannot open {}: {}", path, e)])?;
+    let reader = BufReader::new(file);
+    let table = serde_json::from_reader::<_, LevelTable>(reader)
+        .map_err(|e| vec![format!("invalid JSON: {}", e)])?;
+
+    let errors = validate_level_table(&table);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(table)
+}
+
+pub fn validate_level_table(table: &LevelTable) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if table.thresholds.first() != Some(&0) {
+        errors.push("thresholds must start with 0 for level 1".to_string());
+    }
+    for (idx, pair) in table.thresholds.windows(2).enumerate() {
+        if pair[1] <= pair[0] {
+            errors.push(format!(
+                "thresholds must increase, level {} needs {} but level {} needs {}",
+                idx + 2,
+                pair[1],
+                idx + 1,
+                pair[0]
+            ));
+        }
+    }
+    if table.resources_per_xp <= 0 {
+        errors.push(format!(
+            "resources_per_xp must be positive, got {}",
+            table.resources_per_xp
+        ));
+    }
+    if table.construction_xp < 0 || table.upgrade_xp < 0 {
+        errors.push("construction_xp and upgrade_xp must not be negative".to_string());
+    }
+
+    errors
+}
+
+/// Adds XP and recomputes the level. Returns true on a level up.
+pub fn award_xp(stats: &mut PlayerStats, table: &LevelTable, xp: i32) -> bool {
+    stats.xp += xp;
+    let level = table.level_for(stats.xp);
+    let leveled_up = level > stats.level;
+    stats.level = level;
+
+    leveled_up
+}
+
+/// Awards XP to the player, logging the level up if there is one.
+pub fn reward_player(ecs: &World, xp: i32) {
+    let player = *ecs.fetch::<Entity>();
+    let level = {
+        let mut stats_storage = ecs.write_storage::<PlayerStats>();
+        let player_stats = stats_storage
+            .get_mut(player)
+            .expect("Player must have stats");
+        if !award_xp(player_stats, &ecs.fetch::<LevelTable>(), xp) {
+            return;
+        }
+        player_stats.level
+    };
+
+    gamelog::log(
+        ecs,
+        LogCategory::Progress,
+        format!("You reached level {}!", level),
+    );
+}
+
+/// Turns freshly produced resources into XP, carrying over the remainder.
+pub fn award_production_xp(stats: &mut PlayerStats, table: &LevelTable, produced: i32) -> bool {
+    stats.production_xp_pool += produced;
+    let xp = stats.production_xp_pool / table.resources_per_xp;
+    stats.production_xp_pool %= table.resources_per_xp;
+
+    award_xp(stats, table, xp)
+}
@@ -1,16 +1,92 @@
 use super::components::*;
-use rltk::Rltk;
+use super::{construction_system, Camera, GameClock};
+use rltk::{Rltk, RGB};
 use specs::prelude::*;
 
 pub fn draw_buildings(ecs: &World, ctx: &mut Rltk) {
     let building_storage = ecs.read_storage::<Building>();
     let renderable_storage = ecs.read_storage::<Renderable>();
 
+    let camera = ecs.fetch::<Camera>();
+
     for (building, renderable) in (&building_storage, &renderable_storage).join() {
         for x in building.rect.x1..building.rect.x2 {
             for y in building.rect.y1..building.rect.y2 {
-                ctx.set(x, y, renderable.fg, renderable.bg, renderable.glyph);
+                if let Some((screen_x, screen_y)) = camera.to_screen(x, y) {
+                    ctx.set(
+                        screen_x,
+                        screen_y,
+                        renderable.fg,
+                        renderable.bg,
+                        renderable.glyph,
+                    );
+                }
             }
         }
     }
 }
+
+/// Construction sites fill up with the building's glyph as work progresses;
+/// the rest of the site is scaffolding.
+pub fn draw_construction_sites(ecs: &World, ctx: &mut Rltk) {
+    let site_storage = ecs.read_storage::<ConstructionSite>();
+    let renderable_storage = ecs.read_storage::<Renderable>();
+
+    let camera = ecs.fetch::<Camera>();
+    let current = ecs.fetch::<GameClock>().now();
+
+    for (site, renderable) in (&site_storage, &renderable_storage).join() {
+        let rect = &site.rect;
+        let tiles = (rect.x2 - rect.x1) * (rect.y2 - rect.y1);
+        let built = tiles * construction_system::progress_percent(site, current) / 100;
+        let scaffold = if site.finish_at.is_some() {
+            RGB::named(rltk::GRAY60)
+        } else {
+            RGB::named(rltk::GRAY30)
+        };
+
+        for y in rect.y1..rect.y2 {
+            for x in rect.x1..rect.x2 {
+                if let Some((screen_x, screen_y)) = camera.to_screen(x, y) {
+                    let idx = (y - rect.y1) * (rect.x2 - rect.x1) + x - rect.x1;
+                    if idx < built {
+                        ctx.set(
+                            screen_x,
+                            screen_y,
+                            renderable.fg,
+                            renderable.bg,
+                            renderable.glyph,
+                        );
+                    } else {
+                        ctx.set(
+                            screen_x,
+                            screen_y,
+                            scaffold,
+                            RGB::named(rltk::BLACK),
+                            rltk::to_cp437('░'),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn draw_units(ecs: &World, ctx: &mut Rltk) {
+    let position_storage = ecs.read_storage::<Position>();
+    let renderable_storage = ecs.read_storage::<Renderable>();
+
+    let camera = ecs.fetch::<Camera>();
+
+    for (pos, renderable) in (&position_storage, &renderable_storage).join() {
+        if let Some((screen_x, screen_y)) = camera.to_screen(pos.x, pos.y) {
+            ctx.set(
+                screen_x,
+                screen_y,
+                renderable.fg,
+                renderable.bg,
+                renderable.glyph,
+            );
+        }
+    }
+}
@@ -1,64 +1,1193 @@
-use crate::ResourceType;
+use crate::gamelog::{GameLog, LogCategory};
+use crate::progression::{self, LevelTable};
+use crate::{
+    deposits, utils, ConstructionManifest, GameClock, Map, PopulationSettings, ResourceType,
+};
 
-use super::components;
-use chrono::prelude::*;
-use rltk::console;
+use super::components::{
+    self, Building, Converter, ConverterStatus, Generator, Name, PlayerStats, ResourceHistory,
+    ResourceInfo, Upkeep, Workforce,
+};
 use specs::prelude::*;
-use std::cmp::min;
+use std::cmp::{max, min};
+use std::collections::HashMap;
 
 pub struct ResourceSystem {}
 
 impl<'a> System<'a> for ResourceSystem {
     type SystemData = (
-        ReadStorage<'a, components::Generator>,
+        WriteStorage<'a, components::Generator>,
         WriteStorage<'a, components::PlayerStats>,
         WriteExpect<'a, Entity>,
+        ReadExpect<'a, GameClock>,
+        ReadExpect<'a, LevelTable>,
+        ReadStorage<'a, components::Capacity>,
+        ReadExpect<'a, ConstructionManifest>,
+        WriteStorage<'a, components::Converter>,
+        ReadStorage<'a, components::Housing>,
+        WriteStorage<'a, components::Workforce>,
+        ReadStorage<'a, components::Upkeep>,
+        WriteExpect<'a, Map>,
+        ReadStorage<'a, components::Building>,
+        ReadStorage<'a, components::Name>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, ResourceHistory>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (generators, mut stats, player) = data;
+        let (
+            mut generators,
+            mut stats,
+            player,
+            clock,
+            level_table,
+            capacities,
+            manifest,
+            mut converters,
+            housings,
+            mut workforces,
+            upkeeps,
+            mut map,
+            buildings,
+            names,
+            mut log,
+            mut histories,
+        ) = data;
 
         let player_stats = stats.get_mut(*player).expect("Player must have stats");
-        let current = Local::now().timestamp();
+        apply_caps(player_stats, &manifest, &capacities);
+        apply_housing(
+            player_stats,
+            &manifest.population,
+            &housings,
+            &mut workforces,
+        );
+
+        let current = clock.now();
         let time_elapsed = (current - player_stats.next_refresh) as i32;
 
         if time_elapsed > 0 {
-            let mut food_rate_sum = 0;
-            let mut wood_rate_sum = 0;
-            let mut stone_rate_sum = 0;
-            for generator in generators.join() {
-                match generator.resource_type {
-                    ResourceType::Food => {
-                        food_rate_sum += generator.rate;
-                    }
-                    ResourceType::Wood => {
-                        wood_rate_sum += generator.rate;
-                    }
-                    ResourceType::Stone => {
-                        stone_rate_sum += generator.rate;
-                    }
+            let percent = production_percent(player_stats, &manifest.population);
+            let generator_rates = rate_sums(
+                (&generators, workforces.maybe())
+                    .join()
+                    .filter(|(generator, _)| generator.harvest_radius.is_none()),
+                std::iter::empty(),
+                percent,
+            );
+            let stored_before = total_amount(player_stats);
+
+            let mut lost_to_cap = HashMap::new();
+            for (resource_type, info) in player_stats.resources.iter_mut() {
+                let rate = generator_rates.get(resource_type).copied().unwrap_or(0);
+                let total = info.amount + rate * time_elapsed;
+                if total > info.max_amount {
+                    lost_to_cap.insert(resource_type.clone(), total - info.max_amount);
                 }
+                info.amount = min(info.max_amount, total);
             }
 
-            player_stats.food.amount = min(
-                player_stats.food.max_amount,
-                player_stats.food.amount + food_rate_sum * time_elapsed,
+            let mut terrain_changed = deposits::regrow(&mut map, current);
+            terrain_changed |= harvest_deposits(
+                player_stats,
+                &mut map,
+                &manifest,
+                (&mut generators, workforces.maybe(), &buildings).join(),
+                time_elapsed as i64,
+                percent,
+                current,
             );
-            player_stats.food.rate = food_rate_sum;
+            if terrain_changed {
+                update_yields(
+                    &map,
+                    &manifest,
+                    (&mut generators, &buildings, &names).join(),
+                );
+            }
 
-            player_stats.wood.amount = min(
-                player_stats.wood.max_amount,
-                player_stats.wood.amount + wood_rate_sum * time_elapsed,
-            );
-            player_stats.wood.rate = wood_rate_sum;
+            let mut produced = total_amount(player_stats) - stored_before;
+            for converter in (&mut converters).join() {
+                produced += run_converter(player_stats, converter, time_elapsed as i64);
+            }
+            report_full_storage(player_stats, &manifest, &mut log, current);
+
+            let upkeep = upkeep_sums(player_stats, &manifest.population, upkeeps.join());
+            let starving = pay_upkeep(player_stats, &manifest.population, &upkeep, time_elapsed);
+            starve(player_stats, &manifest.population, starving, time_elapsed);
+            if !starving {
+                grow_population(player_stats, &manifest.population, time_elapsed);
+            }
 
-            player_stats.stone.amount = min(
-                player_stats.stone.max_amount,
-                player_stats.stone.amount + stone_rate_sum * time_elapsed,
+            let net_rates = net_rates(
+                player_stats,
+                &manifest.population,
+                (&generators, workforces.maybe()).join(),
+                converters.join(),
+                upkeeps.join(),
             );
-            player_stats.stone.rate = stone_rate_sum;
+            for (resource_type, info) in player_stats.resources.iter_mut() {
+                info.rate = net_rates.get(resource_type).copied().unwrap_or(0);
+            }
+
+            if progression::award_production_xp(player_stats, &level_table, produced) {
+                log.add(
+                    current,
+                    LogCategory::Progress,
+                    format!("You reached level {}!", player_stats.level),
+                );
+            }
 
             player_stats.next_refresh = current;
+
+            if !histories.contains(*player) {
+                // saves from before the history existed
+                histories
+                    .insert(*player, ResourceHistory::default())
+                    .expect("Unable to insert the resource history");
+            }
+            let history = histories.get_mut(*player).unwrap();
+            for (resource_type, lost) in lost_to_cap {
+                *history.lost_to_cap.entry(resource_type).or_insert(0) += lost as i64;
+            }
+            history.recent.record(player_stats, current);
+            history.long_term.record(player_stats, current);
+        }
+    }
+}
+
+/// Logs every resource that just reached its cap. It is reported again once
+/// it drops below 90% of the cap.
+fn report_full_storage(
+    stats: &components::PlayerStats,
+    manifest: &ConstructionManifest,
+    log: &mut GameLog,
+    current: i64,
+) {
+    for resource in manifest.resources.iter() {
+        let info = match stats.resources.get(&resource.id) {
+            Some(info) if info.max_amount > 0 => info,
+            _ => continue,
+        };
+        if info.amount >= info.max_amount {
+            if log.full_storage.insert(resource.id.clone()) {
+                log.add(
+                    current,
+                    LogCategory::Storage,
+                    format!("{} storage is full.", resource.name),
+                );
+            }
+        } else if info.amount < info.max_amount * 9 / 10 {
+            log.full_storage.remove(&resource.id);
+        }
+    }
+}
+
+/// Derives every `max_amount` from the base cap plus all storage buildings.
+/// Anything above a cap that shrank is lost. Resources the stats don't know
+/// yet, e.g. newly declared in the manifest, start at their starting amount.
+fn apply_caps(
+    stats: &mut components::PlayerStats,
+    manifest: &ConstructionManifest,
+    capacities: &ReadStorage<components::Capacity>,
+) {
+    for resource in manifest.resources.iter() {
+        let mut cap = resource.base_cap;
+        for capacity in capacities.join() {
+            cap += capacity.capacity.get(&resource.id).copied().unwrap_or(0);
+        }
+
+        let info = stats
+            .resources
+            .entry(resource.id.clone())
+            .or_insert_with(|| ResourceInfo {
+                amount: resource.starting_amount,
+                ..Default::default()
+            });
+        info.max_amount = cap;
+        info.amount = min(info.amount, cap);
+    }
+}
+
+/// Derives the housing cap from the base housing plus all housing buildings.
+/// Citizens without a home leave, and workers beyond the remaining
+/// population are sent home.
+fn apply_housing(
+    stats: &mut PlayerStats,
+    settings: &PopulationSettings,
+    housings: &ReadStorage<components::Housing>,
+    workforces: &mut WriteStorage<Workforce>,
+) {
+    stats.housing = settings.base_housing + housings.join().map(|h| h.capacity).sum::<i32>();
+    stats.population = min(stats.population, stats.housing);
+
+    let mut excess = workforces.join().map(|w| w.assigned).sum::<i32>() - stats.population;
+    for workforce in workforces.join() {
+        if excess <= 0 {
+            break;
+        }
+        let released = min(excess, workforce.assigned);
+        workforce.assigned -= released;
+        excess -= released;
+    }
+}
+
+/// Adds a citizen every `growth_seconds` while there is housing and enough
+/// food for them to eat on arrival.
+fn grow_population(stats: &mut PlayerStats, settings: &PopulationSettings, seconds: i32) {
+    if stats.population >= stats.housing {
+        stats.growth_progress = 0;
+        return;
+    }
+
+    stats.growth_progress = stats.growth_progress.saturating_add(seconds);
+    while stats.growth_progress >= settings.growth_seconds && stats.population < stats.housing {
+        match stats.resources.get_mut(&settings.food) {
+            Some(food) if food.amount >= settings.growth_food => {
+                food.amount -= settings.growth_food;
+            }
+            _ => {
+                // wait for food without banking more progress
+                stats.growth_progress = settings.growth_seconds;
+                return;
+            }
+        }
+        stats.population += 1;
+        stats.growth_progress -= settings.growth_seconds;
+    }
+}
+
+/// Food the citizens eat per second.
+pub fn population_upkeep(stats: &PlayerStats, settings: &PopulationSettings) -> i32 {
+    (stats.population + settings.citizens_per_food - 1) / settings.citizens_per_food
+}
+
+/// Everything eaten per second by the citizens and by buildings with upkeep.
+fn upkeep_sums<'a>(
+    stats: &PlayerStats,
+    settings: &PopulationSettings,
+    upkeeps: impl Iterator<Item = &'a Upkeep>,
+) -> HashMap<ResourceType, i32> {
+    let mut sums = HashMap::new();
+    sums.insert(settings.food.clone(), population_upkeep(stats, settings));
+    for upkeep in upkeeps {
+        for (resource_type, rate) in upkeep.costs.iter() {
+            *sums.entry(resource_type.clone()).or_insert(0) += rate;
+        }
+    }
+
+    sums
+}
+
+/// Takes `seconds` worth of upkeep from the stock; whatever cannot be paid is
+/// simply not eaten. Returns true when the food ran out.
+fn pay_upkeep(
+    stats: &mut PlayerStats,
+    settings: &PopulationSettings,
+    upkeep: &HashMap<ResourceType, i32>,
+    seconds: i32,
+) -> bool {
+    let mut starving = false;
+    for (resource_type, rate) in upkeep.iter() {
+        if let Some(info) = stats.resources.get_mut(resource_type) {
+            let cost = rate.saturating_mul(seconds);
+            if cost > info.amount && *resource_type == settings.food {
+                starving = true;
+            }
+            info.amount = max(info.amount - cost, 0);
+        }
+    }
+
+    starving
+}
+
+/// Counts how long the settlement has gone hungry. After the grace period a
+/// citizen leaves every `starvation_loss_seconds`.
+fn starve(stats: &mut PlayerStats, settings: &PopulationSettings, starving: bool, seconds: i32) {
+    if !starving {
+        stats.starving_seconds = 0;
+        return;
+    }
+
+    stats.starving_seconds += seconds;
+    while stats.starving_seconds
+        >= settings.starvation_grace_seconds + settings.starvation_loss_seconds
+        && stats.population > 0
+    {
+        stats.population -= 1;
+        stats.starving_seconds -= settings.starvation_loss_seconds;
+    }
+}
+
+/// Percent of their rate generators produce at; starving workers slack off.
+pub fn production_percent(stats: &PlayerStats, settings: &PopulationSettings) -> i32 {
+    if stats.starving_seconds > 0 {
+        settings.starvation_penalty
+    } else {
+        100
+    }
+}
+
+/// What a generator actually produces per second with its current staff.
+pub fn effective_rate(generator: &Generator, workforce: Option<&Workforce>) -> i32 {
+    if generator.exhausted {
+        return 0;
+    }
+
+    staffed_rate(generator, workforce)
+}
+
+/// What a generator would produce with its current staff and deposits to spare.
+fn staffed_rate(generator: &Generator, workforce: Option<&Workforce>) -> i32 {
+    let rate = generator.rate * generator.yield_percent / 100;
+    let rate = match generator.renovation_percent {
+        Some(percent) => rate * percent / 100,
+        None => rate,
+    };
+    match workforce {
+        Some(workforce) if workforce.slots > 0 => rate * workforce.assigned / workforce.slots,
+        _ => rate,
+    }
+}
+
+/// Lets every harvesting generator draw `seconds` worth of its rate, at
+/// `percent`, from the deposits in range, never more than there is room to
+/// store. Returns whether any deposit was exhausted.
+fn harvest_deposits<'a>(
+    stats: &mut PlayerStats,
+    map: &mut Map,
+    manifest: &ConstructionManifest,
+    harvesters: impl Iterator<Item = (&'a mut Generator, Option<&'a Workforce>, &'a Building)>,
+    seconds: i64,
+    percent: i32,
+    current: i64,
+) -> bool {
+    let mut changed = false;
+    for (generator, workforce, building) in harvesters {
+        let radius = match generator.harvest_radius {
+            Some(radius) => radius,
+            None => continue,
+        };
+        let info = match stats.resources.get_mut(&generator.resource_type) {
+            Some(info) => info,
+            None => continue,
+        };
+
+        let wanted = (staffed_rate(generator, workforce) * percent / 100) as i64 * seconds;
+        let wanted = min(wanted, max(info.max_amount - info.amount, 0) as i64) as i32;
+        if wanted <= 0 {
+            continue;
+        }
+
+        let (taken, exhausted_tiles) = deposits::harvest(
+            map,
+            manifest,
+            &generator.resource_type,
+            &building.rect,
+            radius,
+            wanted,
+            current,
+        );
+        info.amount += taken;
+        generator.exhausted = taken == 0;
+        changed |= exhausted_tiles;
+    }
+
+    changed
+}
+
+/// Recomputes terrain yields after the ground under some buildings changed.
+fn update_yields<'a>(
+    map: &Map,
+    manifest: &ConstructionManifest,
+    generators: impl Iterator<Item = (&'a mut Generator, &'a Building, &'a Name)>,
+) {
+    for (generator, building, name) in generators {
+        if let Some(detail) = manifest.building(&name.name) {
+            generator.yield_percent = utils::terrain_yield(map, detail, &building.rect);
+        }
+    }
+}
+
+fn total_amount(stats: &components::PlayerStats) -> i32 {
+    stats.resources.values().map(|info| info.amount).sum()
+}
+
+/// Net amount of each resource produced per second by the generators, at
+/// `percent` of their rate, and by the converters that are currently running.
+fn rate_sums<'a>(
+    generators: impl Iterator<Item = (&'a Generator, Option<&'a Workforce>)>,
+    converters: impl Iterator<Item = &'a Converter>,
+    percent: i32,
+) -> HashMap<ResourceType, i32> {
+    let mut sums = HashMap::new();
+    for (generator, workforce) in generators {
+        *sums.entry(generator.resource_type.clone()).or_insert(0) +=
+            effective_rate(generator, workforce) * percent / 100;
+    }
+    for converter in converters {
+        if converter.status != ConverterStatus::Running {
+            continue;
+        }
+        for (resource_type, rate) in converter.inputs.iter() {
+            *sums.entry(resource_type.clone()).or_insert(0) -= rate;
+        }
+        for (resource_type, rate) in converter.outputs.iter() {
+            *sums.entry(resource_type.clone()).or_insert(0) += rate;
+        }
+    }
+
+    sums
+}
+
+/// Production minus upkeep, per resource and second.
+fn net_rates<'a>(
+    stats: &PlayerStats,
+    settings: &PopulationSettings,
+    generators: impl Iterator<Item = (&'a Generator, Option<&'a Workforce>)>,
+    converters: impl Iterator<Item = &'a Converter>,
+    upkeeps: impl Iterator<Item = &'a Upkeep>,
+) -> HashMap<ResourceType, i32> {
+    let mut rates = rate_sums(generators, converters, production_percent(stats, settings));
+    for (resource_type, rate) in upkeep_sums(stats, settings, upkeeps) {
+        *rates.entry(resource_type).or_insert(0) -= rate;
+    }
+
+    rates
+}
+
+/// Runs a converter for up to `seconds` cycles. It stalls as soon as an input
+/// runs out (starved) or an output reaches its cap (blocked). Returns the
+/// amount of output produced.
+fn run_converter(stats: &mut PlayerStats, converter: &mut Converter, seconds: i64) -> i32 {
+    if converter.renovating {
+        converter.status = ConverterStatus::Renovating;
+        return 0;
+    }
+
+    let mut cycles = seconds;
+    converter.status = ConverterStatus::Running;
+    for (resource_type, rate) in converter.inputs.iter() {
+        let possible = stats.amount(resource_type) as i64 / *rate as i64;
+        if possible < cycles {
+            cycles = possible;
+            converter.status = ConverterStatus::Starved;
+        }
+    }
+    for (resource_type, rate) in converter.outputs.iter() {
+        let room = stats
+            .resources
+            .get(resource_type)
+            .map_or(0, |info| max(info.max_amount - info.amount, 0));
+        let possible = room as i64 / *rate as i64;
+        if possible < cycles {
+            cycles = possible;
+            converter.status = ConverterStatus::Blocked;
+        }
+    }
+
+    let cycles = cycles as i32;
+    for (resource_type, rate) in converter.inputs.iter() {
+        if let Some(info) = stats.resources.get_mut(resource_type) {
+            info.amount -= rate * cycles;
+        }
+    }
+    let mut produced = 0;
+    for (resource_type, rate) in converter.outputs.iter() {
+        if let Some(info) = stats.resources.get_mut(resource_type) {
+            info.amount += rate * cycles;
+            produced += rate * cycles;
+        }
+    }
+
+    produced
+}
+
+/// Updates the displayed rates right away instead of waiting for the next
+/// refresh, e.g. after a building was removed.
+pub fn refresh_rates(ecs: &mut World) {
+    let generators = ecs.read_storage::<components::Generator>();
+    let converters = ecs.read_storage::<components::Converter>();
+    let workforces = ecs.read_storage::<components::Workforce>();
+    let upkeeps = ecs.read_storage::<components::Upkeep>();
+    let mut stats = ecs.write_storage::<components::PlayerStats>();
+    let manifest = ecs.fetch::<ConstructionManifest>();
+    let player = *ecs.fetch::<Entity>();
+
+    let player_stats = stats.get_mut(player).expect("Player must have stats");
+    let net_rates = net_rates(
+        player_stats,
+        &manifest.population,
+        (&generators, workforces.maybe()).join(),
+        converters.join(),
+        upkeeps.join(),
+    );
+    for (resource_type, info) in player_stats.resources.iter_mut() {
+        info.rate = net_rates.get(resource_type).copied().unwrap_or(0);
+    }
+}
+
+/// What every type of building adds to or takes from each resource per
+/// second right now, with how many of them there are. Sorted by name.
+pub fn production_by_building(ecs: &World) -> Vec<(String, i32, HashMap<ResourceType, i32>)> {
+    let names = ecs.read_storage::<Name>();
+    let buildings = ecs.read_storage::<Building>();
+    let generators = ecs.read_storage::<Generator>();
+    let workforces = ecs.read_storage::<Workforce>();
+    let converters = ecs.read_storage::<Converter>();
+    let upkeeps = ecs.read_storage::<Upkeep>();
+    let stats = ecs.read_storage::<PlayerStats>();
+    let player = *ecs.fetch::<Entity>();
+    let manifest = ecs.fetch::<ConstructionManifest>();
+
+    let player_stats = stats.get(player).expect("Player must have stats");
+    let percent = production_percent(player_stats, &manifest.population);
+    let mut types: HashMap<String, (i32, HashMap<ResourceType, i32>)> = HashMap::new();
+    for (entity, name, _) in (&ecs.entities(), &names, &buildings).join() {
+        let (count, rates) = types.entry(name.name.clone()).or_default();
+        *count += 1;
+        if let Some(generator) = generators.get(entity) {
+            *rates.entry(generator.resource_type.clone()).or_insert(0) +=
+                effective_rate(generator, workforces.get(entity)) * percent / 100;
+        }
+        if let Some(converter) = converters.get(entity) {
+            if converter.status == ConverterStatus::Running {
+                for (resource_type, rate) in converter.inputs.iter() {
+                    *rates.entry(resource_type.clone()).or_insert(0) -= rate;
+                }
+                for (resource_type, rate) in converter.outputs.iter() {
+                    *rates.entry(resource_type.clone()).or_insert(0) += rate;
+                }
+            }
+        }
+        if let Some(upkeep) = upkeeps.get(entity) {
+            for (resource_type, rate) in upkeep.costs.iter() {
+                *rates.entry(resource_type.clone()).or_insert(0) -= rate;
+            }
+        }
+    }
+
+    let mut breakdown: Vec<(String, i32, HashMap<ResourceType, i32>)> = types
+        .into_iter()
+        .map(|(name, (count, rates))| (name, count, rates))
+        .collect();
+    breakdown.sort_by(|a, b| a.0.cmp(&b.0));
+
+    breakdown
+}
+
+#[derive(Default, Copy, Clone)]
+pub struct OfflineYield {
+    pub produced: i32,
+    pub wasted: i32,   // lost at the storage cap
+    pub consumed: i32, // fed into converters or eaten as upkeep
+}
+
+/// What the settlement produced between the last save and the current load.
+#[derive(Default, Clone)]
+pub struct OfflineReport {
+    pub elapsed: i64, // second
+    pub yields: HashMap<ResourceType, OfflineYield>,
+}
+
+fn accrue_offline(
+    info: &mut ResourceInfo,
+    rate: i32,
+    seconds: i64,
+    efficiency: i32,
+) -> OfflineYield {
+    let produced = rate as i64 * seconds * efficiency as i64 / 100;
+    let produced = produced.clamp(0, i32::MAX as i64) as i32;
+    let stored = min(produced, max(info.max_amount - info.amount, 0));
+
+    info.amount += stored;
+
+    OfflineYield {
+        produced,
+        wasted: produced - stored,
+        consumed: 0,
+    }
+}
+
+fn resource_amounts(stats: &PlayerStats) -> HashMap<ResourceType, i32> {
+    stats
+        .resources
+        .iter()
+        .map(|(resource_type, info)| (resource_type.clone(), info.amount))
+        .collect()
+}
+
+/// Books how the stock moved since `before`: gains as produced, losses as
+/// consumed.
+fn tally_offline(
+    report: &mut OfflineReport,
+    stats: &PlayerStats,
+    before: HashMap<ResourceType, i32>,
+) {
+    for (resource_type, before) in before {
+        let delta = stats.amount(&resource_type) - before;
+        let offline_yield = report.yields.entry(resource_type).or_default();
+        if delta > 0 {
+            offline_yield.produced += delta;
+        } else {
+            offline_yield.consumed -= delta;
+        }
+    }
+}
+
+/// Moves every pending timer `seconds` later, so time beyond the offline cap
+/// is skipped as if it never passed and no catch-up loop has to replay it.
+fn skip_offline_time(ecs: &mut World, seconds: i64) {
+    for stats in (&mut ecs.write_storage::<PlayerStats>()).join() {
+        stats.next_refresh += seconds;
+    }
+    for site in (&mut ecs.write_storage::<components::ConstructionSite>()).join() {
+        site.queued_at += seconds;
+        if let Some(finish_at) = site.finish_at.as_mut() {
+            *finish_at += seconds;
+        }
+    }
+    for renovation in (&mut ecs.write_storage::<components::Renovation>()).join() {
+        renovation.finish_at += seconds;
+    }
+    for garrison in (&mut ecs.write_storage::<components::Garrison>()).join() {
+        garrison.next_soldier += seconds;
+    }
+    for raider in (&mut ecs.write_storage::<components::Raider>()).join() {
+        raider.next_move += seconds;
+    }
+    for schedule in (&mut ecs.write_storage::<components::RaidSchedule>()).join() {
+        schedule.next_raid += seconds;
+    }
+    for regrowth in ecs.write_resource::<Map>().regrowing.iter_mut() {
+        regrowth.at += seconds;
+    }
+}
+
+/// Credits the player with everything the generators produced since the save
+/// was written, honouring the storage caps and the manifest's offline settings.
+/// Time beyond `max_seconds` is skipped for every timer, not just production.
+pub fn apply_offline_progress(ecs: &mut World) -> OfflineReport {
+    {
+        let player = *ecs.fetch::<Entity>();
+        let next_refresh = ecs
+            .read_storage::<PlayerStats>()
+            .get(player)
+            .expect("Player must have stats")
+            .next_refresh;
+        let elapsed = ecs.fetch::<GameClock>().now() - next_refresh;
+        let max_seconds = ecs.fetch::<ConstructionManifest>().offline.max_seconds;
+        if let Some(max_seconds) = max_seconds {
+            if elapsed > max_seconds {
+                skip_offline_time(ecs, elapsed - max_seconds);
+            }
         }
     }
+
+    let manifest = ecs.fetch::<ConstructionManifest>();
+    let settings = manifest.offline;
+    let level_table = ecs.fetch::<LevelTable>();
+    let mut generators = ecs.write_storage::<components::Generator>();
+    let buildings = ecs.read_storage::<components::Building>();
+    let names = ecs.read_storage::<components::Name>();
+    let mut map = ecs.write_resource::<Map>();
+    let capacities = ecs.read_storage::<components::Capacity>();
+    let mut converters = ecs.write_storage::<components::Converter>();
+    let housings = ecs.read_storage::<components::Housing>();
+    let mut workforces = ecs.write_storage::<components::Workforce>();
+    let upkeeps = ecs.read_storage::<components::Upkeep>();
+    let mut stats = ecs.write_storage::<components::PlayerStats>();
+    let player = *ecs.fetch::<Entity>();
+
+    let player_stats = stats.get_mut(player).expect("Player must have stats");
+    apply_caps(player_stats, &manifest, &capacities);
+    apply_housing(
+        player_stats,
+        &manifest.population,
+        &housings,
+        &mut workforces,
+    );
+
+    let current = ecs.fetch::<GameClock>().now();
+    // even without a cap, keep the per-second arithmetic below in range
+    let elapsed = (current - player_stats.next_refresh).clamp(0, i32::MAX as i64);
+
+    let percent = production_percent(player_stats, &manifest.population);
+    let generator_rates = rate_sums(
+        (&generators, workforces.maybe())
+            .join()
+            .filter(|(generator, _)| generator.harvest_radius.is_none()),
+        std::iter::empty(),
+        percent,
+    );
+
+    let mut report = OfflineReport {
+        elapsed,
+        ..Default::default()
+    };
+    for (resource_type, info) in player_stats.resources.iter_mut() {
+        let rate = generator_rates.get(resource_type).copied().unwrap_or(0);
+        let offline_yield = accrue_offline(info, rate, elapsed, settings.efficiency);
+        report.yields.insert(resource_type.clone(), offline_yield);
+    }
+
+    // harvesters and converters run at the same efficiency; their output
+    // only ever fills free room, so nothing is wasted
+    let effective_seconds = elapsed * settings.efficiency as i64 / 100;
+    let before = resource_amounts(player_stats);
+    let mut terrain_changed = deposits::regrow(&mut map, current);
+    terrain_changed |= harvest_deposits(
+        player_stats,
+        &mut map,
+        &manifest,
+        (&mut generators, workforces.maybe(), &buildings).join(),
+        effective_seconds,
+        percent,
+        current,
+    );
+    if terrain_changed {
+        update_yields(
+            &map,
+            &manifest,
+            (&mut generators, &buildings, &names).join(),
+        );
+    }
+    tally_offline(&mut report, player_stats, before);
+    // a converter's inputs and outputs are booked separately, so what it
+    // made doesn't cancel out what it used
+    for converter in (&mut converters).join() {
+        let before = resource_amounts(player_stats);
+        run_converter(player_stats, converter, effective_seconds);
+        tally_offline(&mut report, player_stats, before);
+    }
+
+    // upkeep is eaten at the same efficiency, but nobody starves to death
+    // while the player is away
+    let upkeep = upkeep_sums(player_stats, &manifest.population, upkeeps.join());
+    let before = resource_amounts(player_stats);
+    let starving = pay_upkeep(
+        player_stats,
+        &manifest.population,
+        &upkeep,
+        effective_seconds as i32,
+    );
+    tally_offline(&mut report, player_stats, before);
+    if !starving {
+        grow_population(player_stats, &manifest.population, elapsed as i32);
+    }
+
+    let net_rates = net_rates(
+        player_stats,
+        &manifest.population,
+        (&generators, workforces.maybe()).join(),
+        converters.join(),
+        upkeeps.join(),
+    );
+    for (resource_type, info) in player_stats.resources.iter_mut() {
+        info.rate = net_rates.get(resource_type).copied().unwrap_or(0);
+    }
+    player_stats.next_refresh = current;
+
+    if let Some(history) = ecs.write_storage::<ResourceHistory>().get_mut(player) {
+        for (resource_type, offline_yield) in report.yields.iter() {
+            if offline_yield.wasted > 0 {
+                *history
+                    .lost_to_cap
+                    .entry(resource_type.clone())
+                    .or_insert(0) += offline_yield.wasted as i64;
+            }
+        }
+    }
+
+    let produced = report
+        .yields
+        .values()
+        .map(|resource| resource.produced - resource.wasted)
+        .sum();
+    progression::award_production_xp(player_stats, &level_table, produced);
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{advance, test_world};
+
+    fn wood(ecs: &World) -> i32 {
+        let player = *ecs.fetch::<Entity>();
+        ecs.read_storage::<PlayerStats>()
+            .get(player)
+            .unwrap()
+            .amount("wood")
+    }
+
+    fn stats(ecs: &World) -> PlayerStats {
+        let player = *ecs.fetch::<Entity>();
+        ecs.read_storage::<PlayerStats>()
+            .get(player)
+            .unwrap()
+            .clone()
+    }
+
+    fn set_amount(ecs: &mut World, id: &str, amount: i32) {
+        let player = *ecs.fetch::<Entity>();
+        let mut stats = ecs.write_storage::<PlayerStats>();
+        stats
+            .get_mut(player)
+            .unwrap()
+            .resources
+            .get_mut(id)
+            .unwrap()
+            .amount = amount;
+    }
+
+    #[test]
+    fn generators_produce_for_the_time_that_passed() {
+        let mut ecs = test_world();
+        ecs.create_entity()
+            .with(Generator {
+                rate: 3,
+                resource_type: "wood".to_string(),
+                yield_percent: 100,
+                harvest_radius: None,
+                exhausted: false,
+                renovation_percent: None,
+            })
+            .build();
+        ResourceSystem {}.run_now(&ecs);
+        let before = wood(&ecs);
+
+        ResourceSystem {}.run_now(&ecs);
+        assert_eq!(wood(&ecs), before);
+
+        advance(&mut ecs, 10);
+        ResourceSystem {}.run_now(&ecs);
+        assert_eq!(wood(&ecs), before + 30);
+    }
+
+    #[test]
+    fn production_past_the_cap_is_counted_as_lost() {
+        let mut ecs = test_world();
+        ecs.create_entity()
+            .with(Generator {
+                rate: 3,
+                resource_type: "wood".to_string(),
+                yield_percent: 100,
+                harvest_radius: None,
+                exhausted: false,
+                renovation_percent: None,
+            })
+            .build();
+        ResourceSystem {}.run_now(&ecs);
+        let player = *ecs.fetch::<Entity>();
+        {
+            let mut stats = ecs.write_storage::<PlayerStats>();
+            let wood = stats
+                .get_mut(player)
+                .unwrap()
+                .resources
+                .get_mut("wood")
+                .unwrap();
+            wood.amount = wood.max_amount - 10;
+        }
+
+        advance(&mut ecs, 10);
+        ResourceSystem {}.run_now(&ecs);
+        advance(&mut ecs, 2);
+        ResourceSystem {}.run_now(&ecs);
+
+        let stats = ecs.read_storage::<PlayerStats>();
+        let wood = &stats.get(player).unwrap().resources["wood"];
+        assert_eq!(wood.amount, wood.max_amount);
+        let histories = ecs.read_storage::<ResourceHistory>();
+        let history = histories.get(player).unwrap();
+        assert_eq!(history.lost_to_cap["wood"], 20 + 6);
+    }
+
+    #[test]
+    fn uncapped_offline_progress_survives_a_huge_gap() {
+        let mut ecs = test_world();
+        ecs.write_resource::<ConstructionManifest>()
+            .offline
+            .max_seconds = None;
+        let player = *ecs.fetch::<Entity>();
+        let population_before = {
+            let mut stats = ecs.write_storage::<PlayerStats>();
+            let stats = stats.get_mut(player).unwrap();
+            // about a century of wall time
+            stats.next_refresh -= 3_200_000_000;
+            stats.population
+        };
+
+        let report = apply_offline_progress(&mut ecs);
+        assert_eq!(report.elapsed, i32::MAX as i64);
+        let stats = ecs.read_storage::<PlayerStats>();
+        let stats = stats.get(player).unwrap();
+        assert!(stats.population >= population_before);
+        for info in stats.resources.values() {
+            assert!(info.amount >= 0 && info.amount <= info.max_amount);
+        }
+    }
+
+    #[test]
+    fn storage_buildings_raise_the_cap_until_they_are_gone() {
+        let mut ecs = test_world();
+        ResourceSystem {}.run_now(&ecs);
+        assert_eq!(stats(&ecs).resources["wood"].max_amount, 10000);
+
+        let depot = ecs
+            .create_entity()
+            .with(components::Capacity {
+                capacity: HashMap::from([("wood".to_string(), 5000)]),
+            })
+            .build();
+        ResourceSystem {}.run_now(&ecs);
+        assert_eq!(stats(&ecs).resources["wood"].max_amount, 15000);
+        assert_eq!(stats(&ecs).resources["stone"].max_amount, 10000);
+        set_amount(&mut ecs, "wood", 12000);
+
+        ecs.delete_entity(depot).unwrap();
+        ecs.maintain();
+        ResourceSystem {}.run_now(&ecs);
+        let wood = &stats(&ecs).resources["wood"];
+        assert_eq!(wood.max_amount, 10000);
+        assert_eq!(wood.amount, 10000);
+    }
+
+    fn sawmill() -> Converter {
+        Converter {
+            inputs: HashMap::from([("wood".to_string(), 4)]),
+            outputs: HashMap::from([("planks".to_string(), 2)]),
+            status: ConverterStatus::Running,
+            renovating: false,
+        }
+    }
+
+    #[test]
+    fn converter_turns_inputs_into_outputs_every_second() {
+        let mut ecs = test_world();
+        ResourceSystem {}.run_now(&ecs);
+        set_amount(&mut ecs, "wood", 100);
+        let mut stats = stats(&ecs);
+        let mut converter = sawmill();
+
+        assert_eq!(run_converter(&mut stats, &mut converter, 10), 20);
+        assert_eq!(converter.status, ConverterStatus::Running);
+        assert_eq!(stats.amount("wood"), 60);
+        assert_eq!(stats.amount("planks"), 20);
+    }
+
+    #[test]
+    fn converter_starves_when_an_input_runs_out() {
+        let mut ecs = test_world();
+        ResourceSystem {}.run_now(&ecs);
+        set_amount(&mut ecs, "wood", 10);
+        let mut stats = stats(&ecs);
+        let mut converter = sawmill();
+
+        assert_eq!(run_converter(&mut stats, &mut converter, 10), 4);
+        assert_eq!(converter.status, ConverterStatus::Starved);
+        assert_eq!(stats.amount("wood"), 2);
+        assert_eq!(stats.amount("planks"), 4);
+    }
+
+    #[test]
+    fn converter_is_blocked_when_an_output_is_full() {
+        let mut ecs = test_world();
+        ResourceSystem {}.run_now(&ecs);
+        set_amount(&mut ecs, "wood", 100);
+        set_amount(&mut ecs, "planks", 4995);
+        let mut stats = stats(&ecs);
+        let mut converter = sawmill();
+
+        assert_eq!(run_converter(&mut stats, &mut converter, 10), 4);
+        assert_eq!(converter.status, ConverterStatus::Blocked);
+        assert_eq!(stats.amount("wood"), 92);
+        assert_eq!(stats.amount("planks"), 4999);
+    }
+
+    #[test]
+    fn converter_stands_still_while_renovating() {
+        let mut ecs = test_world();
+        ResourceSystem {}.run_now(&ecs);
+        set_amount(&mut ecs, "wood", 100);
+        let mut stats = stats(&ecs);
+        let mut converter = sawmill();
+        converter.renovating = true;
+
+        assert_eq!(run_converter(&mut stats, &mut converter, 10), 0);
+        assert_eq!(converter.status, ConverterStatus::Renovating);
+        assert_eq!(stats.amount("wood"), 100);
+    }
+
+    fn settings(ecs: &World) -> PopulationSettings {
+        ecs.fetch::<ConstructionManifest>().population.clone()
+    }
+
+    #[test]
+    fn citizens_arrive_while_there_is_food_and_housing() {
+        let ecs = test_world();
+        let settings = settings(&ecs);
+        let mut stats = stats(&ecs);
+        stats.population = 5;
+        stats.housing = 7;
+        stats.resources.get_mut("food").unwrap().amount = 200;
+
+        grow_population(&mut stats, &settings, 45);
+        assert_eq!(stats.population, 7);
+        assert_eq!(stats.amount("food"), 100);
+
+        // no room left, so no progress is banked either
+        grow_population(&mut stats, &settings, 45);
+        assert_eq!(stats.population, 7);
+        assert_eq!(stats.growth_progress, 0);
+    }
+
+    #[test]
+    fn growth_waits_for_food_without_banking_progress() {
+        let ecs = test_world();
+        let settings = settings(&ecs);
+        let mut stats = stats(&ecs);
+        stats.population = 5;
+        stats.housing = 10;
+        stats.resources.get_mut("food").unwrap().amount = settings.growth_food - 1;
+
+        grow_population(&mut stats, &settings, 100);
+        assert_eq!(stats.population, 5);
+        assert_eq!(stats.growth_progress, settings.growth_seconds);
+    }
+
+    #[test]
+    fn citizens_without_a_home_leave_and_take_their_jobs_with_them() {
+        let mut ecs = test_world();
+        let farm = ecs
+            .create_entity()
+            .with(Workforce {
+                slots: 8,
+                assigned: 8,
+            })
+            .build();
+        let camp = ecs
+            .create_entity()
+            .with(Workforce {
+                slots: 4,
+                assigned: 4,
+            })
+            .build();
+        let player = *ecs.fetch::<Entity>();
+        ecs.write_storage::<PlayerStats>()
+            .get_mut(player)
+            .unwrap()
+            .population = 12;
+
+        ResourceSystem {}.run_now(&ecs);
+        let stats = stats(&ecs);
+        assert_eq!(stats.housing, settings(&ecs).base_housing);
+        assert_eq!(stats.population, 10);
+        let workforces = ecs.read_storage::<Workforce>();
+        assert_eq!(
+            workforces.get(farm).unwrap().assigned + workforces.get(camp).unwrap().assigned,
+            10
+        );
+    }
+
+    #[test]
+    fn output_scales_with_the_workers_assigned() {
+        let generator = Generator {
+            rate: 8,
+            resource_type: "food".to_string(),
+            yield_percent: 100,
+            harvest_radius: None,
+            exhausted: false,
+            renovation_percent: None,
+        };
+        let half = Workforce {
+            slots: 4,
+            assigned: 2,
+        };
+        let none = Workforce {
+            slots: 4,
+            assigned: 0,
+        };
+
+        assert_eq!(effective_rate(&generator, None), 8);
+        assert_eq!(effective_rate(&generator, Some(&half)), 4);
+        assert_eq!(effective_rate(&generator, Some(&none)), 0);
+    }
+
+    #[test]
+    fn citizens_and_buildings_eat_their_upkeep() {
+        let ecs = test_world();
+        let settings = settings(&ecs);
+        let mut stats = stats(&ecs);
+        stats.population = 12;
+        let army = Upkeep {
+            costs: HashMap::from([("food".to_string(), 3)]),
+        };
+        let upkeep = upkeep_sums(&stats, &settings, std::iter::once(&army));
+        // 12 citizens at 5 per food round up to 3
+        assert_eq!(upkeep["food"], 6);
+
+        stats.resources.get_mut("food").unwrap().amount = 100;
+        assert!(!pay_upkeep(&mut stats, &settings, &upkeep, 10));
+        assert_eq!(stats.amount("food"), 40);
+        assert!(pay_upkeep(&mut stats, &settings, &upkeep, 10));
+        assert_eq!(stats.amount("food"), 0);
+    }
+
+    #[test]
+    fn citizens_leave_once_the_starvation_grace_is_over() {
+        let ecs = test_world();
+        let settings = settings(&ecs);
+        let mut stats = stats(&ecs);
+        stats.population = 5;
+
+        starve(
+            &mut stats,
+            &settings,
+            true,
+            settings.starvation_grace_seconds,
+        );
+        assert_eq!(stats.population, 5);
+        starve(
+            &mut stats,
+            &settings,
+            true,
+            settings.starvation_loss_seconds * 2,
+        );
+        assert_eq!(stats.population, 3);
+
+        starve(&mut stats, &settings, false, 1);
+        assert_eq!(stats.starving_seconds, 0);
+        assert_eq!(stats.population, 3);
+    }
+
+    #[test]
+    fn starving_workers_produce_at_the_penalty_rate() {
+        let mut ecs = test_world();
+        ecs.create_entity()
+            .with(Generator {
+                rate: 10,
+                resource_type: "wood".to_string(),
+                yield_percent: 100,
+                harvest_radius: None,
+                exhausted: false,
+                renovation_percent: None,
+            })
+            .build();
+        ResourceSystem {}.run_now(&ecs);
+        set_amount(&mut ecs, "food", 0);
+        let before = wood(&ecs);
+
+        // the food runs out during this refresh, so it still produces in full
+        advance(&mut ecs, 10);
+        ResourceSystem {}.run_now(&ecs);
+        assert_eq!(wood(&ecs), before + 100);
+        assert_eq!(stats(&ecs).starving_seconds, 10);
+
+        advance(&mut ecs, 10);
+        ResourceSystem {}.run_now(&ecs);
+        let penalty = settings(&ecs).starvation_penalty;
+        assert_eq!(wood(&ecs), before + 100 + 100 * penalty / 100);
+    }
 }
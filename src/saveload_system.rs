@@ -0,0 +1,295 @@
+use super::components::*;
+use super::{map_builder, Clock, GameClock, Map, WallClock, MAP_COUNT};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::saveload::{
+    DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker, SimpleMarkerAllocator,
+};
+use std::cmp::max;
+use std::convert::Infallible;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub const SAVE_PATH: &str = "./savegame.json";
+/// Bumped whenever a change to the saved components would make older saves
+/// load wrongly; such saves are refused instead.
+pub const SAVE_VERSION: u32 = 3;
+
+/// Written ahead of the components.
+#[derive(Serialize, Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// Why the last save or load failed, shown on the main menu.
+#[derive(Default)]
+pub struct SaveNotice {
+    pub notice: Option<String>,
+}
+
+macro_rules! serialize_individually {
+    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        SerializeComponents::<Infallible, SimpleMarker<SerializeMe>>::serialize(
+            &( $ecs.read_storage::<$type>(), ),
+            &$data.0,
+            &$data.1,
+            &mut $ser,
+        )
+        .map_err(|e| format!("cannot write {}: {}", stringify!($type), e))?;
+        )*
+    };
+}
+
+macro_rules! deserialize_individually {
+    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        DeserializeComponents::<Infallible, _>::deserialize(
+            &mut ( &mut $ecs.write_storage::<$type>(), ),
+            &$data.0, // entities
+            &mut $data.1, // marker
+            &mut $data.2, // allocater
+            &mut $de,
+        )
+        .map_err(|e| format!("cannot read {}: {}", stringify!($type), e))?;
+        )*
+    };
+}
+
+pub fn save_game(ecs: &mut World) -> Result<(), String> {
+    let file =
+        File::create(SAVE_PATH).map_err(|e| format!("cannot create {}: {}", SAVE_PATH, e))?;
+    let mut writer = BufWriter::new(file);
+    write_world(ecs, &mut writer)?;
+    writer
+        .flush()
+        .map_err(|e| format!("cannot write {}: {}", SAVE_PATH, e))
+}
+
+fn write_world<W: Write>(ecs: &mut World, writer: W) -> Result<(), String> {
+    // the map and the clock are resources, so they ride along on a
+    // temporary helper entity
+    let map_copy = Map::clone(&ecs.fetch::<Map>());
+    let sim_time = ecs.fetch::<GameClock>().now_millis();
+    let save_helper = ecs
+        .create_entity()
+        .with(SerializationHelper {
+            map: map_copy,
+            sim_time,
+            wall_time: WallClock.now_millis(),
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    let result = serialize_world(ecs, writer);
+
+    ecs.delete_entity(save_helper)
+        .expect("Failed to clean up the serialization helper");
+
+    result
+}
+
+fn serialize_world<W: Write>(ecs: &World, writer: W) -> Result<(), String> {
+    let data = (
+        ecs.entities(),
+        ecs.read_storage::<SimpleMarker<SerializeMe>>(),
+    );
+
+    let mut serializer = serde_json::Serializer::new(writer);
+    SaveHeader {
+        version: SAVE_VERSION,
+    }
+    .serialize(&mut serializer)
+    .map_err(|e| format!("cannot write the header: {}", e))?;
+    serialize_individually!(
+        ecs,
+        serializer,
+        data,
+        PlayerStats,
+        ResourceHistory,
+        Building,
+        Generator,
+        Capacity,
+        Converter,
+        Housing,
+        Workforce,
+        Upkeep,
+        Garrison,
+        ConstructionSite,
+        Renovation,
+        Position,
+        Raider,
+        RaidSchedule,
+        Renderable,
+        Name,
+        SerializationHelper
+    );
+
+    Ok(())
+}
+
+pub fn does_save_exist() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+/// Replaces the current world with the saved one. When the save can't be
+/// read the current world is put back as it was; if even that fails, both
+/// reasons are reported and only a new game can follow.
+pub fn load_game(ecs: &mut World) -> Result<(), String> {
+    let data = fs::read(SAVE_PATH).map_err(|e| format!("cannot read {}: {}", SAVE_PATH, e))?;
+
+    let mut backup = Vec::new();
+    write_world(ecs, &mut backup)?;
+    if let Err(error) = read_world(ecs, &data) {
+        return match read_world(ecs, &backup) {
+            Ok(()) => Err(error),
+            Err(restore_error) => Err(format!(
+                "{} (the current game could not be restored either: {})",
+                error, restore_error
+            )),
+        };
+    }
+
+    Ok(())
+}
+
+fn read_world(ecs: &mut World, data: &[u8]) -> Result<(), String> {
+    let mut de = serde_json::Deserializer::from_slice(data);
+    match SaveHeader::deserialize(&mut de) {
+        Ok(header) if header.version == SAVE_VERSION => {}
+        Ok(header) if header.version > SAVE_VERSION => {
+            return Err(format!(
+                "the save is from a newer version of the game (format {})",
+                header.version
+            ))
+        }
+        _ => return Err("the save is from an older version of the game".to_string()),
+    }
+
+    // drop the current world before restoring the saved one
+    {
+        let to_delete: Vec<Entity> = ecs.entities().join().collect();
+        for entity in to_delete {
+            ecs.delete_entity(entity).expect("Deletion failed");
+        }
+    }
+
+    {
+        let mut d = (
+            &mut ecs.entities(),
+            &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(),
+            &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>(),
+        );
+
+        deserialize_individually!(
+            ecs,
+            de,
+            d,
+            PlayerStats,
+            ResourceHistory,
+            Building,
+            Generator,
+            Capacity,
+            Converter,
+            Housing,
+            Workforce,
+            Upkeep,
+            Garrison,
+            ConstructionSite,
+            Renovation,
+            Position,
+            Raider,
+            RaidSchedule,
+            Renderable,
+            Name,
+            SerializationHelper
+        );
+    }
+
+    let mut helper_entity = None;
+    {
+        let entities = ecs.entities();
+        let helper_storage = ecs.read_storage::<SerializationHelper>();
+        let stats_storage = ecs.read_storage::<PlayerStats>();
+
+        for (entity, helper) in (&entities, &helper_storage).join() {
+            let mut map = ecs.write_resource::<Map>();
+            *map = helper.map.clone();
+            map_builder::rebuild_terrain(&mut map);
+            map.tile_content = vec![Vec::new(); MAP_COUNT];
+            // the game clock picks up where the save left off, plus the real
+            // time that has passed since, which counts as offline progress
+            let offline = max(WallClock.now_millis() - helper.wall_time, 0);
+            ecs.write_resource::<GameClock>()
+                .set_now_millis(helper.sim_time + offline);
+            helper_entity = Some(entity);
+        }
+
+        for (entity, _stats) in (&entities, &stats_storage).join() {
+            let mut player = ecs.write_resource::<Entity>();
+            *player = entity;
+        }
+    }
+
+    match helper_entity {
+        Some(helper) => ecs
+            .delete_entity(helper)
+            .expect("Failed to clean up the serialization helper"),
+        None => return Err("the save has no map".to_string()),
+    }
+    if ecs.read_storage::<PlayerStats>().join().next().is_none() {
+        return Err("the save has no player".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_world;
+
+    fn level(ecs: &World) -> i32 {
+        let player = *ecs.fetch::<Entity>();
+        ecs.read_storage::<PlayerStats>().get(player).unwrap().level
+    }
+
+    #[test]
+    fn saved_world_loads_back() {
+        let mut ecs = test_world();
+        let player = *ecs.fetch::<Entity>();
+        ecs.write_storage::<PlayerStats>()
+            .get_mut(player)
+            .unwrap()
+            .level = 4;
+        let mut data = Vec::new();
+        write_world(&mut ecs, &mut data).unwrap();
+
+        let mut loaded = test_world();
+        read_world(&mut loaded, &data).unwrap();
+        assert_eq!(level(&loaded), 4);
+    }
+
+    #[test]
+    fn saves_without_a_version_are_refused() {
+        let mut ecs = test_world();
+        let mut data = Vec::new();
+        write_world(&mut ecs, &mut data).unwrap();
+        // the components alone, as written before saves had a header
+        let header_end = data.iter().position(|b| *b == b'}').unwrap() + 1;
+
+        let error = read_world(&mut ecs, &data[header_end..]).unwrap_err();
+        assert!(error.contains("older version"));
+        assert_eq!(level(&ecs), 1);
+    }
+
+    #[test]
+    fn missing_player_fields_fall_back_to_defaults() {
+        let stats: PlayerStats = serde_json::from_str(r#"{"next_refresh": 5}"#).unwrap();
+        assert_eq!(stats.next_refresh, 5);
+        assert_eq!(stats.level, 1);
+        assert_eq!(stats.xp, 0);
+        assert!(stats.resources.is_empty());
+    }
+}
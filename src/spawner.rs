@@ -1,155 +1,138 @@
-use crate::{BuildingDetail, ConstructionManifest};
+use crate::{utils, BuildingDetail, ConstructionManifest, GameClock};
 
-use super::{components::*, Map, Rect, ResourceType};
-use rltk::{RandomNumberGenerator, RGB};
+use super::{components::*, Map, Rect};
+use rltk::RGB;
 use specs::{
     prelude::*,
     saveload::{MarkedBuilder, SimpleMarker},
 };
 
-pub fn get_spawner(
-    ecs: &mut World,
-    idx: usize,
-) -> (
-    BuildingDetail,
-    impl Fn(&mut World, BuildingDetail, i32, i32),
-) {
-    let detail = ecs.fetch::<ConstructionManifest>().buildings[idx].clone();
-
-    let func = match detail.name.as_str() {
-        "Farm" => spawn_farm,
-        "Food Factory" => spawn_food_factory,
-        "Army" => spawn_army,
-        "Lumber Camp" => spawn_lumber_camp,
-        "Mining Camp" => spawn_mining_camp,
-        _ => panic!("Unmatched building name"),
+pub fn player(ecs: &mut World) -> Entity {
+    let current = ecs.fetch::<GameClock>().now();
+    let (resources, population, first_raid) = {
+        let manifest = ecs.fetch::<ConstructionManifest>();
+        let resources = manifest
+            .resources
+            .iter()
+            .map(|resource| {
+                let info = ResourceInfo {
+                    amount: resource.starting_amount,
+                    max_amount: resource.base_cap,
+                    rate: 0,
+                };
+                (resource.id.clone(), info)
+            })
+            .collect();
+        (
+            resources,
+            manifest.population.clone(),
+            manifest.raids.first_after_seconds,
+        )
     };
 
-    (detail, func)
-}
-
-pub fn spawn_farm(ecs: &mut World, detail: BuildingDetail, x: i32, y: i32) {
-    {
-        let mut map = ecs.write_resource::<Map>();
-        let idx = map.xy_idx(x, y);
-        map.occupied[idx] = true;
-    }
-
-    let rect = Rect::new(x, y, detail.width, detail.height);
     ecs.create_entity()
-        .with(Renderable {
-            glyph: rltk::to_cp437('☼'),
-            fg: RGB::named(rltk::WHEAT3),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Building { rect, level: 0 })
-        .with(Generator {
-            rate: detail.levels.get(&0).unwrap().rate.unwrap(),
-            resource_type: ResourceType::Food,
+        .with(PlayerStats {
+            resources,
+            next_refresh: current - 1,
+            level: 1,
+            xp: 0,
+            production_xp_pool: 0,
+            population: population.starting,
+            housing: population.base_housing,
+            growth_progress: 0,
+            starving_seconds: 0,
+            founded_at: current,
         })
-        .with(Name {
-            name: detail.name.to_string(),
+        .with(RaidSchedule {
+            next_raid: current + first_raid as i64,
+            raids: 0,
         })
-        .build();
+        .with(ResourceHistory::default())
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-pub fn spawn_food_factory(ecs: &mut World, detail: BuildingDetail, x: i32, y: i32) {
-    {
-        let mut map = ecs.write_resource::<Map>();
-        let idx = map.xy_idx(x, y);
-        map.occupied[idx] = true;
-    }
+/// Queues `detail` for construction at `x`, `y`. The site waits for a free
+/// builder and is turned into the building by `finish_construction`.
+pub fn spawn_construction_site(ecs: &mut World, detail: &BuildingDetail, x: i32, y: i32) -> Entity {
+    let current = ecs.fetch::<GameClock>().now();
+    let cost = detail.levels[&0]
+        .requirements
+        .as_ref()
+        .map(|req| req.resources.clone())
+        .unwrap_or_default();
 
-    let rect = Rect::new(x, y, detail.width, detail.height);
     ecs.create_entity()
         .with(Renderable {
-            glyph: rltk::to_cp437('o'),
-            fg: RGB::named(rltk::LIME),
-            bg: RGB::named(rltk::BLACK),
+            glyph: rltk::to_cp437(detail.glyph),
+            fg: utils::parse_color(&detail.fg).unwrap_or_else(|| RGB::named(rltk::WHITE)),
+            bg: utils::parse_color(&detail.bg).unwrap_or_else(|| RGB::named(rltk::BLACK)),
             render_order: 0,
         })
-        .with(Building { rect, level: 0 })
-        .with(Generator {
-            rate: detail.levels.get(&0).unwrap().rate.unwrap(),
-            resource_type: ResourceType::Food,
-        })
-        .with(Name {
+        .with(ConstructionSite {
             name: detail.name.to_string(),
+            rect: Rect::new(x, y, detail.width, detail.height),
+            seconds: detail.levels[&0].build_seconds,
+            queued_at: current,
+            finish_at: None,
+            cost,
         })
-        .build();
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-pub fn spawn_army(ecs: &mut World, detail: BuildingDetail, x: i32, y: i32) {
-    {
-        let mut map = ecs.write_resource::<Map>();
-        let idx = map.xy_idx(x, y);
-        map.occupied[idx] = true;
-    }
-
+/// Builds a finished building purely from its manifest entry.
+pub fn spawn_building(ecs: &mut World, detail: &BuildingDetail, x: i32, y: i32) -> Entity {
+    let current = ecs.fetch::<GameClock>().now();
     let rect = Rect::new(x, y, detail.width, detail.height);
-    ecs.create_entity()
-        .with(Renderable {
-            glyph: rltk::to_cp437('x'),
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Building { rect, level: 0 })
-        .with(Name {
-            name: detail.name.to_string(),
-        })
-        .build();
-}
-
-pub fn spawn_lumber_camp(ecs: &mut World, detail: BuildingDetail, x: i32, y: i32) {
-    {
+    let yield_percent = {
         let mut map = ecs.write_resource::<Map>();
         let idx = map.xy_idx(x, y);
         map.occupied[idx] = true;
-    }
+        utils::terrain_yield(&map, detail, &rect)
+    };
 
-    let rect = Rect::new(x, y, detail.width, detail.height);
-    ecs.create_entity()
+    let mut builder = ecs
+        .create_entity()
         .with(Renderable {
-            glyph: rltk::to_cp437('╣'),
-            fg: RGB::named(rltk::TAN4),
-            bg: RGB::named(rltk::BLACK),
+            glyph: rltk::to_cp437(detail.glyph),
+            fg: utils::parse_color(&detail.fg).unwrap_or_else(|| RGB::named(rltk::WHITE)),
+            bg: utils::parse_color(&detail.bg).unwrap_or_else(|| RGB::named(rltk::BLACK)),
             render_order: 0,
         })
         .with(Building { rect, level: 0 })
-        .with(Generator {
-            rate: detail.levels.get(&0).unwrap().rate.unwrap(),
-            resource_type: ResourceType::Wood,
-        })
         .with(Name {
             name: detail.name.to_string(),
-        })
-        .build();
-}
+        });
 
-pub fn spawn_mining_camp(ecs: &mut World, detail: BuildingDetail, x: i32, y: i32) {
-    {
-        let mut map = ecs.write_resource::<Map>();
-        let idx = map.xy_idx(x, y);
-        map.occupied[idx] = true;
+    if let (Some(resource_type), Some(rate)) = (&detail.resource_type, detail.levels[&0].rate) {
+        builder = builder.with(Generator {
+            rate,
+            resource_type: resource_type.clone(),
+            yield_percent,
+            harvest_radius: detail.harvest_radius,
+            exhausted: false,
+            renovation_percent: None,
+        });
+    }
+    if let Some(storage) = utils::capacity_for(detail, 0) {
+        builder = builder.with(storage);
+    }
+    if let Some(converter) = utils::converter_for(detail, 0) {
+        builder = builder.with(converter);
+    }
+    if let Some(housing) = utils::housing_for(detail, 0) {
+        builder = builder.with(housing);
+    }
+    if let Some(upkeep) = utils::upkeep_for(detail, 0) {
+        builder = builder.with(upkeep);
+    }
+    if let Some(slots) = detail.levels[&0].workers {
+        builder = builder.with(Workforce { slots, assigned: 0 });
+    }
+    if let Some(training) = &detail.levels[&0].training {
+        builder = builder.with(utils::garrison_for(training, current));
     }
 
-    let rect = Rect::new(x, y, detail.width, detail.height);
-    ecs.create_entity()
-        .with(Renderable {
-            glyph: rltk::to_cp437('■'),
-            fg: RGB::named(rltk::GRAY60),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Building { rect, level: 0 })
-        .with(Generator {
-            rate: detail.levels.get(&0).unwrap().rate.unwrap(),
-            resource_type: ResourceType::Stone,
-        })
-        .with(Name {
-            name: detail.name.to_string(),
-        })
-        .build();
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
 }
@@ -0,0 +1,42 @@
+//! A small world for driving the systems with a clock the tests control.
+
+use specs::prelude::*;
+use specs::saveload::SimpleMarkerAllocator;
+
+use crate::gamelog::GameLog;
+use crate::{manifest, progression, spawner};
+use crate::{GameClock, ManualClock, Map, SerializeMe, MANIFEST_PATH};
+
+/// Game time the test worlds start at, in seconds.
+pub const START: i64 = 1_000_000;
+
+/// Everything the systems need, loaded from the shipped manifest and level
+/// table, with the player spawned at `START`.
+pub fn test_world() -> World {
+    let mut ecs = World::new();
+    crate::register_components(&mut ecs);
+    ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+    ecs.insert(GameClock::new(ManualClock::new(START * 1000, 0)));
+    ecs.insert(manifest::load_manifest(MANIFEST_PATH).expect("The manifest must load"));
+    ecs.insert(
+        progression::load_level_table(progression::LEVEL_TABLE_PATH)
+            .expect("The level table must load"),
+    );
+    ecs.insert(Map::new());
+    ecs.insert(GameLog::new(START));
+    let player = spawner::player(&mut ecs);
+    ecs.insert(player);
+
+    ecs
+}
+
+/// Moves the game clock `seconds` forward.
+pub fn advance(ecs: &mut World, seconds: i64) {
+    let mut clock = ecs.write_resource::<GameClock>();
+    let current = clock.now_millis();
+    clock.set_now_millis(current + seconds * 1000);
+}
+
+pub fn now(ecs: &World) -> i64 {
+    ecs.fetch::<GameClock>().now()
+}
@@ -1,63 +1,85 @@
 use super::components::*;
 use rltk::RGB;
-use serde::{Deserialize, Serialize};
-use specs::error::NoError;
 use specs::prelude::*;
-use specs::saveload::{ConvertSaveload, Marker};
-use specs_derive::*;
 
 use lazy_static::lazy_static;
-use std::error::Error;
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::sync::Once;
 
-use crate::{BuildingDetail, PlayerStats, PlayerStatsSaveloadData};
+use crate::gamelog::{self, LogCategory};
+use crate::progression::{self, LevelTable};
+use crate::{
+    resource_system, BuildingDetail, ConstructionManifest, GameClock, Map, PlayerStats, Rect,
+    ResourceType, TrainingDetail,
+};
 
 lazy_static! {
     pub static ref MORANDI_RED: RGB = RGB::from_u8(185, 87, 86);
 }
 
-pub fn requirements_check(
+static PALETTE_INIT: Once = Once::new();
+
+/// Accepts rltk colour names in any case ("GOLD2", "gold2") or hex codes ("#ffd700").
+pub fn parse_color(name: &str) -> Option<RGB> {
+    if name.starts_with('#') {
+        return RGB::from_hex(name).ok();
+    }
+
+    PALETTE_INIT.call_once(rltk::add_named_colors_to_palette);
+    rltk::palette_color(name.to_lowercase()).map(|color| color.to_rgb())
+}
+
+/// Why `next_level` of a building can't be paid for yet, if it can't.
+pub fn requirements_problem(
     stats: &PlayerStats,
     building: Option<&Building>,
     detail: &BuildingDetail,
     next_level: i32,
-) -> bool {
+    manifest: &ConstructionManifest,
+) -> Option<String> {
     if next_level >= detail.levels.len() as i32 {
-        return false;
+        return Some("already at the highest level".to_string());
     }
 
-    if let Some(req) = detail.levels[&next_level].requirements {
+    if let Some(req) = &detail.levels[&next_level].requirements {
         if let Some(req_building_level) = req.current_building_level {
-            if let Some(b) = building {
-                if b.level != req_building_level {
-                    return false;
-                }
-            } else {
-                return false;
+            match building {
+                Some(b) if b.level == req_building_level => {}
+                _ => return Some(format!("needs building level {}", req_building_level)),
             }
         }
 
-        // #TODO
-        // if let Some(req_player_level) = req.current_player_level {
-        // }
-
-        if let Some(req_food) = req.food {
-            if stats.food.amount < req_food {
-                return false;
+        if let Some(req_player_level) = req.current_player_level {
+            if stats.level < req_player_level {
+                return Some(format!("needs player level {}", req_player_level));
             }
         }
-        if let Some(req_wood) = req.wood {
-            if stats.wood.amount < req_wood {
-                return false;
-            }
-        }
-        if let Some(req_stone) = req.stone {
-            if stats.stone.amount < req_stone {
-                return false;
+
+        for resource in manifest.resources.iter() {
+            let amount = req.resources.get(&resource.id).copied().unwrap_or(0);
+            if stats.amount(&resource.id) < amount {
+                return Some(format!(
+                    "not enough {} ({} / {})",
+                    resource.name,
+                    stats.amount(&resource.id),
+                    amount
+                ));
             }
         }
     }
 
-    true
+    None
+}
+
+pub fn requirements_check(
+    stats: &PlayerStats,
+    building: Option<&Building>,
+    detail: &BuildingDetail,
+    next_level: i32,
+    manifest: &ConstructionManifest,
+) -> bool {
+    requirements_problem(stats, building, detail, next_level, manifest).is_none()
 }
 
 pub fn consume_resource(stats: &mut PlayerStats, detail: &BuildingDetail, next_level: i32) {
@@ -65,27 +87,684 @@ pub fn consume_resource(stats: &mut PlayerStats, detail: &BuildingDetail, next_l
         panic!("next level is out of range");
     }
 
-    if let Some(req) = detail.levels[&next_level].requirements {
-        if let Some(req_food) = req.food {
-            stats.food.amount -= req_food;
+    if let Some(req) = &detail.levels[&next_level].requirements {
+        for (resource_type, amount) in req.resources.iter() {
+            if let Some(info) = stats.resources.get_mut(resource_type) {
+                info.amount -= amount;
+            }
         }
-        if let Some(req_wood) = req.wood {
-            stats.wood.amount -= req_wood;
+    }
+}
+
+/// Extra storage a building provides at `level`, if it is a storage building.
+pub fn capacity_for(detail: &BuildingDetail, level: i32) -> Option<Capacity> {
+    detail
+        .levels
+        .get(&level)
+        .and_then(|l| l.storage.as_ref())
+        .map(|storage| Capacity {
+            capacity: storage.clone(),
+        })
+}
+
+/// Conversion a building runs at `level`, if it is a converter.
+pub fn converter_for(detail: &BuildingDetail, level: i32) -> Option<Converter> {
+    let level = detail.levels.get(&level)?;
+    match (&level.inputs, &level.outputs) {
+        (Some(inputs), Some(outputs)) => Some(Converter {
+            inputs: inputs.clone(),
+            outputs: outputs.clone(),
+            status: ConverterStatus::Running,
+            renovating: false,
+        }),
+        _ => None,
+    }
+}
+
+/// Housing a building provides at `level`, if it houses citizens.
+pub fn housing_for(detail: &BuildingDetail, level: i32) -> Option<Housing> {
+    detail
+        .levels
+        .get(&level)
+        .and_then(|l| l.housing)
+        .map(|capacity| Housing { capacity })
+}
+
+/// Upkeep a building pays at `level`, if any.
+pub fn upkeep_for(detail: &BuildingDetail, level: i32) -> Option<Upkeep> {
+    detail
+        .levels
+        .get(&level)
+        .and_then(|l| l.upkeep.as_ref())
+        .map(|costs| Upkeep {
+            costs: costs.clone(),
+        })
+}
+
+/// An empty garrison that trains its first soldier `seconds` after `current`.
+pub fn garrison_for(training: &TrainingDetail, current: i64) -> Garrison {
+    Garrison {
+        soldiers: 0,
+        capacity: training.max_garrison,
+        seconds: training.seconds,
+        cost: training.cost.clone(),
+        next_soldier: current + training.seconds as i64,
+    }
+}
+
+/// Why a building can't go on `rect`, judged by the ground alone.
+pub fn placement_problem(map: &Map, detail: &BuildingDetail, rect: &Rect) -> Option<String> {
+    if !map.is_buildable(rect) {
+        return Some("Can't build on cliffs or water".to_string());
+    }
+
+    let rule = detail.placement.as_ref()?;
+    let found = map.count_tiles(rect, rule.radius, rule.tile);
+    if found >= rule.min_tiles {
+        return None;
+    }
+
+    let place = match rule.radius {
+        0 => "under it".to_string(),
+        1 => "on or next to it".to_string(),
+        radius => format!("within {} tiles", radius),
+    };
+    Some(format!(
+        "Needs {} {} {} (found {})",
+        rule.min_tiles,
+        rule.tile.name(),
+        place,
+        found
+    ))
+}
+
+/// Percentage of its rate a generator on `rect` produces thanks to the ground.
+pub fn terrain_yield(map: &Map, detail: &BuildingDetail, rect: &Rect) -> i32 {
+    match &detail.placement {
+        Some(rule) => {
+            let extra = max(
+                map.count_tiles(rect, rule.radius, rule.tile) - rule.min_tiles,
+                0,
+            );
+            100 + min(extra * rule.bonus_per_tile, rule.max_bonus)
+        }
+        None => 100,
+    }
+}
+
+/// Citizens not working in any building.
+pub fn idle_workers(stats: &PlayerStats, workforces: &ReadStorage<Workforce>) -> i32 {
+    let assigned: i32 = workforces.join().map(|w| w.assigned).sum();
+    max(stats.population - assigned, 0)
+}
+
+/// Moves up to `delta` idle citizens into a building, or `-delta` workers out
+/// of it when negative. Returns how many actually moved.
+pub fn assign_workers(ecs: &mut World, entity: Entity, delta: i32) -> i32 {
+    let player = *ecs.fetch::<Entity>();
+    let stats_storage = ecs.read_storage::<PlayerStats>();
+    let player_stats = stats_storage.get(player).expect("Player must have stats");
+    let idle = {
+        let workforces = ecs.read_storage::<Workforce>();
+        idle_workers(player_stats, &workforces)
+    };
+
+    let mut workforces = ecs.write_storage::<Workforce>();
+    let workforce = match workforces.get_mut(entity) {
+        Some(workforce) => workforce,
+        None => return 0,
+    };
+    let moved = if delta > 0 {
+        min(delta, min(idle, workforce.slots - workforce.assigned))
+    } else {
+        max(delta, -workforce.assigned)
+    };
+    workforce.assigned += moved;
+
+    moved
+}
+
+/// Pays for the next level of a building and starts its renovation, or
+/// applies the level at once when it takes no time. Fails with the reason
+/// when the requirements are not met or an upgrade is already under way.
+pub fn upgrade_building(ecs: &mut World, entity: Entity) -> Result<(), String> {
+    let manifest = ecs.fetch::<ConstructionManifest>();
+    let detail = {
+        let name_storage = ecs.read_storage::<Name>();
+        let name = name_storage.get(entity).expect("Building must have a name");
+        if ecs.read_storage::<Renovation>().contains(entity) {
+            return Err(format!("The {} is already being upgraded.", name.name));
         }
-        if let Some(req_stone) = req.stone {
-            stats.stone.amount -= req_stone;
+        match manifest.building(&name.name) {
+            Some(detail) => detail,
+            None => return Err(format!("The {} can't be upgraded.", name.name)),
         }
+    };
+
+    let next_level = {
+        let building_storage = ecs.read_storage::<Building>();
+        let mut stats_storage = ecs.write_storage::<PlayerStats>();
+        let player = *ecs.fetch::<Entity>();
+
+        let building = building_storage.get(entity).expect("Building must exist");
+        let player_stats = stats_storage
+            .get_mut(player)
+            .expect("Player must have stats");
+
+        let next_level = building.level + 1;
+        if let Some(problem) =
+            requirements_problem(player_stats, Some(building), detail, next_level, &manifest)
+        {
+            return Err(format!("Can't upgrade the {}: {}.", detail.name, problem));
+        }
+
+        consume_resource(player_stats, detail, next_level);
+        next_level
+    };
+
+    let seconds = detail.levels[&next_level].build_seconds;
+    if seconds == 0 {
+        apply_level(ecs, entity, detail, next_level);
+        progression::reward_player(ecs, ecs.fetch::<LevelTable>().upgrade_xp);
+        gamelog::log(
+            ecs,
+            LogCategory::Construction,
+            format!("The {} reached level {}.", detail.name, next_level),
+        );
+        return Ok(());
     }
+
+    let current = ecs.fetch::<GameClock>().now();
+    let cost = detail.levels[&next_level]
+        .requirements
+        .as_ref()
+        .map(|req| req.resources.clone())
+        .unwrap_or_default();
+    ecs.write_storage::<Renovation>()
+        .insert(
+            entity,
+            Renovation {
+                level: next_level,
+                seconds,
+                finish_at: current + seconds as i64,
+                cost,
+            },
+        )
+        .expect("Unable to start the renovation");
+    // the current level again, now with production cut back
+    apply_level(ecs, entity, detail, next_level - 1);
+
+    Ok(())
+}
+
+/// What finishing a renovation right now costs, in the manifest's instant
+/// resource: a fixed amount for every started minute left.
+pub fn instant_finish_cost(
+    manifest: &ConstructionManifest,
+    renovation: &Renovation,
+    current: i64,
+) -> Option<(ResourceType, i32)> {
+    let resource_type = manifest.upgrades.instant_resource.clone()?;
+    let minutes = ((max(renovation.finish_at - current, 0) + 59) / 60) as i32;
+    Some((
+        resource_type,
+        max(minutes, 1) * manifest.upgrades.instant_cost_per_minute,
+    ))
 }
 
-pub fn upgrade_building(
+/// Pays to finish a building's renovation at once. The upgrade itself lands
+/// on the next run of the systems. Fails with the reason when there is
+/// nothing to finish or it can't be paid for.
+pub fn finish_upgrade_now(ecs: &mut World, entity: Entity) -> Result<(), String> {
+    let current = ecs.fetch::<GameClock>().now();
+    let manifest = ecs.fetch::<ConstructionManifest>();
+    let mut renovation_storage = ecs.write_storage::<Renovation>();
+    let renovation = match renovation_storage.get_mut(entity) {
+        Some(renovation) => renovation,
+        None => return Err("There is no upgrade to finish.".to_string()),
+    };
+    let (resource_type, cost) = match instant_finish_cost(&manifest, renovation, current) {
+        Some(price) => price,
+        None => return Err("Upgrades can't be finished early.".to_string()),
+    };
+
+    let player = *ecs.fetch::<Entity>();
+    let mut stats_storage = ecs.write_storage::<PlayerStats>();
+    let stats = stats_storage
+        .get_mut(player)
+        .expect("Player must have stats");
+    match stats.resources.get_mut(&resource_type) {
+        Some(info) if info.amount >= cost => info.amount -= cost,
+        _ => {
+            return Err(format!(
+                "Can't finish the upgrade: not enough {} ({} / {}).",
+                manifest.resource_name(&resource_type),
+                stats.amount(&resource_type),
+                cost
+            ))
+        }
+    }
+    renovation.finish_at = current;
+
+    Ok(())
+}
+
+/// Puts a building at `level` and brings every per-level component (rates,
+/// storage, conversion, housing, workers, upkeep, training) in line with the
+/// manifest entry.
+pub fn apply_level(ecs: &World, entity: Entity, detail: &BuildingDetail, level: i32) {
+    let mut building_storage = ecs.write_storage::<Building>();
+    let mut generator_storage = ecs.write_storage::<Generator>();
+    let mut capacity_storage = ecs.write_storage::<Capacity>();
+    let mut converter_storage = ecs.write_storage::<Converter>();
+    let mut housing_storage = ecs.write_storage::<Housing>();
+    let mut workforce_storage = ecs.write_storage::<Workforce>();
+    let mut upkeep_storage = ecs.write_storage::<Upkeep>();
+    let mut garrison_storage = ecs.write_storage::<Garrison>();
+    let level_detail = &detail.levels[&level];
+    let renovating = ecs.read_storage::<Renovation>().contains(entity);
+    let renovation_percent = renovating.then(|| {
+        ecs.fetch::<ConstructionManifest>()
+            .upgrades
+            .production_percent
+    });
+
+    if let Some(building) = building_storage.get_mut(entity) {
+        building.level = level;
+    }
+
+    match (&detail.resource_type, level_detail.rate) {
+        (Some(resource_type), Some(rate)) => {
+            let yield_percent = match building_storage.get(entity) {
+                Some(building) => terrain_yield(&ecs.fetch::<Map>(), detail, &building.rect),
+                None => 100,
+            };
+            generator_storage
+                .insert(
+                    entity,
+                    Generator {
+                        rate,
+                        resource_type: resource_type.clone(),
+                        yield_percent,
+                        harvest_radius: detail.harvest_radius,
+                        exhausted: generator_storage
+                            .get(entity)
+                            .is_some_and(|generator| generator.exhausted),
+                        renovation_percent,
+                    },
+                )
+                .expect("Unable to update generator");
+        }
+        _ => {
+            generator_storage.remove(entity);
+        }
+    }
+
+    match capacity_for(detail, level) {
+        Some(capacity) => {
+            capacity_storage
+                .insert(entity, capacity)
+                .expect("Unable to update storage");
+        }
+        None => {
+            capacity_storage.remove(entity);
+        }
+    }
+
+    match (
+        converter_for(detail, level),
+        converter_storage.get_mut(entity),
+    ) {
+        (Some(next), Some(converter)) => {
+            converter.inputs = next.inputs;
+            converter.outputs = next.outputs;
+            converter.renovating = renovating;
+            converter.status = if renovating {
+                ConverterStatus::Renovating
+            } else {
+                ConverterStatus::Running
+            };
+        }
+        (Some(next), None) => {
+            converter_storage
+                .insert(
+                    entity,
+                    Converter {
+                        renovating,
+                        status: if renovating {
+                            ConverterStatus::Renovating
+                        } else {
+                            ConverterStatus::Running
+                        },
+                        ..next
+                    },
+                )
+                .expect("Unable to update converter");
+        }
+        (None, _) => {
+            converter_storage.remove(entity);
+        }
+    }
+
+    match housing_for(detail, level) {
+        Some(housing) => {
+            housing_storage
+                .insert(entity, housing)
+                .expect("Unable to update housing");
+        }
+        None => {
+            housing_storage.remove(entity);
+        }
+    }
+
+    match (level_detail.workers, workforce_storage.get_mut(entity)) {
+        (Some(slots), Some(workforce)) => {
+            workforce.slots = slots;
+            workforce.assigned = min(workforce.assigned, slots);
+        }
+        (Some(slots), None) => {
+            workforce_storage
+                .insert(entity, Workforce { slots, assigned: 0 })
+                .expect("Unable to update workforce");
+        }
+        (None, _) => {
+            workforce_storage.remove(entity);
+        }
+    }
+
+    match upkeep_for(detail, level) {
+        Some(upkeep) => {
+            upkeep_storage
+                .insert(entity, upkeep)
+                .expect("Unable to update upkeep");
+        }
+        None => {
+            upkeep_storage.remove(entity);
+        }
+    }
+
+    match (&level_detail.training, garrison_storage.get_mut(entity)) {
+        (Some(training), Some(garrison)) => {
+            garrison.capacity = training.max_garrison;
+            garrison.soldiers = min(garrison.soldiers, training.max_garrison);
+            garrison.seconds = training.seconds;
+            garrison.cost = training.cost.clone();
+        }
+        (Some(training), None) => {
+            let current = ecs.fetch::<GameClock>().now();
+            garrison_storage
+                .insert(entity, garrison_for(training, current))
+                .expect("Unable to update garrison");
+        }
+        (None, _) => {
+            garrison_storage.remove(entity);
+        }
+    }
+}
+
+/// The building whose top-left corner sits on (x, y), if any.
+pub fn building_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let entities = ecs.entities();
+    let building_storage = ecs.read_storage::<Building>();
+
+    (&entities, &building_storage)
+        .join()
+        .find(|(_, building)| building.rect.x1 == x && building.rect.y1 == y)
+        .map(|(entity, _)| entity)
+}
+
+/// The building covering a world tile, if any.
+pub fn building_under(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let entities = ecs.entities();
+    let building_storage = ecs.read_storage::<Building>();
+
+    (&entities, &building_storage)
+        .join()
+        .find(|(_, building)| building.rect.contains(x, y))
+        .map(|(entity, _)| entity)
+}
+
+/// The construction site covering a world tile, if any.
+pub fn site_under(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let entities = ecs.entities();
+    let site_storage = ecs.read_storage::<ConstructionSite>();
+
+    (&entities, &site_storage)
+        .join()
+        .find(|(_, site)| site.rect.contains(x, y))
+        .map(|(entity, _)| entity)
+}
+
+/// Resources handed back when a building at `level` is torn down: `percent`
+/// of what was paid for its construction and every upgrade since.
+pub fn tear_down_refund(
     detail: &BuildingDetail,
-    building: &mut Building,
-    generator: Option<&mut Generator>,
-    next_level: i32,
-) {
-    building.level = next_level;
-    if let Some(gen) = generator {
-        (*gen).rate = detail.levels[&next_level].rate.unwrap();
+    level: i32,
+    percent: i32,
+) -> HashMap<ResourceType, i32> {
+    let mut paid: HashMap<ResourceType, i32> = HashMap::new();
+    for paid_level in 0..=level {
+        if let Some(req) = detail
+            .levels
+            .get(&paid_level)
+            .and_then(|l| l.requirements.as_ref())
+        {
+            for (resource_type, amount) in req.resources.iter() {
+                *paid.entry(resource_type.clone()).or_insert(0) += amount;
+            }
+        }
+    }
+
+    paid.into_iter()
+        .map(|(resource_type, amount)| (resource_type, amount * percent / 100))
+        .collect()
+}
+
+/// Everything tearing a building down hands back: an upgrade still under
+/// way in full, plus the share of the completed levels.
+pub fn tear_down_total_refund(
+    ecs: &World,
+    entity: Entity,
+    detail: &BuildingDetail,
+    building: &Building,
+    percent: i32,
+) -> HashMap<ResourceType, i32> {
+    let mut refund = tear_down_refund(detail, building.level, percent);
+    if let Some(renovation) = ecs.read_storage::<Renovation>().get(entity) {
+        for (resource_type, amount) in renovation.cost.iter() {
+            *refund.entry(resource_type.clone()).or_insert(0) += amount;
+        }
+    }
+    refund
+}
+
+pub fn tear_down_building(ecs: &mut World, entity: Entity) {
+    cancel_upgrade(ecs, entity, "the building was torn down");
+    {
+        let building_storage = ecs.read_storage::<Building>();
+        let name_storage = ecs.read_storage::<Name>();
+        let building = building_storage.get(entity).expect("Building must exist");
+        let name = name_storage.get(entity).expect("Building must have a name");
+
+        gamelog::log(
+            ecs,
+            LogCategory::Construction,
+            format!("The {} was torn down.", name.name),
+        );
+        let manifest = ecs.fetch::<ConstructionManifest>();
+        if let Some(detail) = manifest.buildings.iter().find(|b| b.name == name.name) {
+            let refund = tear_down_refund(detail, building.level, manifest.tear_down_refund);
+
+            let player = *ecs.fetch::<Entity>();
+            let mut stats_storage = ecs.write_storage::<PlayerStats>();
+            let stats = stats_storage
+                .get_mut(player)
+                .expect("Player must have stats");
+            for (resource_type, amount) in refund {
+                if let Some(info) = stats.resources.get_mut(&resource_type) {
+                    info.amount = min(info.max_amount, info.amount + amount);
+                }
+            }
+        }
+    }
+
+    remove_building(ecs, entity);
+}
+
+/// Cancels a construction site, handing back everything paid for it.
+pub fn cancel_construction(ecs: &mut World, entity: Entity) {
+    {
+        let site_storage = ecs.read_storage::<ConstructionSite>();
+        let site = site_storage.get(entity).expect("Site must exist");
+        gamelog::log(
+            ecs,
+            LogCategory::Construction,
+            format!("Construction of the {} was cancelled.", site.name),
+        );
+
+        let player = *ecs.fetch::<Entity>();
+        let mut stats_storage = ecs.write_storage::<PlayerStats>();
+        let stats = stats_storage
+            .get_mut(player)
+            .expect("Player must have stats");
+        for (resource_type, amount) in site.cost.iter() {
+            if let Some(info) = stats.resources.get_mut(resource_type) {
+                info.amount = min(info.max_amount, info.amount + amount);
+            }
+        }
+    }
+
+    ecs.delete_entity(entity)
+        .expect("Unable to delete the construction site");
+}
+
+/// Stops a building's upgrade, handing back everything paid for it, and puts
+/// the building back to full production at its current level.
+pub fn cancel_upgrade(ecs: &mut World, entity: Entity, reason: &str) {
+    let renovation = match ecs.write_storage::<Renovation>().remove(entity) {
+        Some(renovation) => renovation,
+        None => return,
+    };
+    let name = match ecs.read_storage::<Name>().get(entity) {
+        Some(name) => name.name.clone(),
+        None => return,
+    };
+    gamelog::log(
+        ecs,
+        LogCategory::Failure,
+        format!(
+            "The upgrade of the {} to level {} was cancelled: {}.",
+            name, renovation.level, reason
+        ),
+    );
+
+    {
+        let player = *ecs.fetch::<Entity>();
+        let mut stats_storage = ecs.write_storage::<PlayerStats>();
+        let stats = stats_storage
+            .get_mut(player)
+            .expect("Player must have stats");
+        for (resource_type, amount) in renovation.cost.iter() {
+            if let Some(info) = stats.resources.get_mut(resource_type) {
+                info.amount = min(info.max_amount, info.amount + amount);
+            }
+        }
+    }
+
+    let level = ecs.read_storage::<Building>().get(entity).map(|b| b.level);
+    let manifest = ecs.fetch::<ConstructionManifest>();
+    if let (Some(detail), Some(level)) = (manifest.building(&name), level) {
+        if detail.levels.contains_key(&level) {
+            apply_level(ecs, entity, detail, level);
+        }
+    }
+}
+
+/// Takes a building off the map without any refund.
+pub fn remove_building(ecs: &mut World, entity: Entity) {
+    {
+        let building_storage = ecs.read_storage::<Building>();
+        let building = building_storage.get(entity).expect("Building must exist");
+        let mut map = ecs.write_resource::<Map>();
+        let idx = map.xy_idx(building.rect.x1, building.rect.y1);
+        map.occupied[idx] = false;
+    }
+
+    ecs.delete_entity(entity)
+        .expect("Unable to delete the building");
+    resource_system::refresh_rates(ecs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{now, test_world};
+
+    fn farm(ecs: &mut World, level: i32) -> Entity {
+        let detail = ecs
+            .fetch::<ConstructionManifest>()
+            .building("Farm")
+            .unwrap()
+            .clone();
+        let building = crate::spawner::spawn_building(ecs, &detail, 10, 10);
+        apply_level(ecs, building, &detail, level);
+        building
+    }
+
+    fn food(ecs: &World) -> i32 {
+        let player = *ecs.fetch::<Entity>();
+        ecs.read_storage::<PlayerStats>()
+            .get(player)
+            .unwrap()
+            .amount("food")
+    }
+
+    #[test]
+    fn tearing_down_refunds_a_share_of_the_completed_levels() {
+        let mut ecs = test_world();
+        let building = farm(&mut ecs, 1);
+        let food_before = food(&ecs);
+
+        tear_down_building(&mut ecs, building);
+        ecs.maintain();
+        assert!(!ecs.is_alive(building));
+        // Level 1 cost 100 food; the shipped refund is 50%.
+        assert_eq!(food(&ecs), food_before + 50);
+    }
+
+    #[test]
+    fn tearing_down_mid_upgrade_refunds_the_upgrade_in_full() {
+        let mut ecs = test_world();
+        let building = farm(&mut ecs, 0);
+        let finish_at = now(&ecs) + 60;
+        ecs.write_storage::<Renovation>()
+            .insert(
+                building,
+                Renovation {
+                    level: 1,
+                    seconds: 60,
+                    finish_at,
+                    cost: HashMap::from([("food".to_string(), 100)]),
+                },
+            )
+            .unwrap();
+        let food_before = food(&ecs);
+
+        let refund = {
+            let manifest = ecs.fetch::<ConstructionManifest>();
+            let detail = manifest.building("Farm").unwrap();
+            let buildings = ecs.read_storage::<Building>();
+            tear_down_total_refund(
+                &ecs,
+                building,
+                detail,
+                buildings.get(building).unwrap(),
+                manifest.tear_down_refund,
+            )
+        };
+        assert_eq!(refund.get("food"), Some(&100));
+
+        tear_down_building(&mut ecs, building);
+        assert_eq!(food(&ecs), food_before + 100);
     }
 }